@@ -0,0 +1 @@
+ALTER TABLE schemas ADD COLUMN schema_type CHARACTER VARYING NOT NULL DEFAULT 'AVRO';
@@ -0,0 +1,11 @@
+CREATE SEQUENCE schema_references_id_seq;
+CREATE TABLE schema_references (
+  id BIGINT PRIMARY KEY DEFAULT nextval('schema_references_id_seq'::regclass),
+  schema_id BIGINT NOT NULL references schemas(id) on delete cascade,
+  name TEXT NOT NULL,
+  subject TEXT NOT NULL,
+  version INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX index_schema_references_on_schema_id_and_name ON schema_references(schema_id, name);
+CREATE INDEX index_schema_references_on_subject_and_version ON schema_references(subject, version);
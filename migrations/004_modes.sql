@@ -0,0 +1,11 @@
+CREATE SEQUENCE modes_id_seq;
+CREATE TABLE modes (
+  id BIGINT PRIMARY KEY DEFAULT nextval('modes_id_seq'::regclass),
+  mode CHARACTER VARYING NOT NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  subject_id BIGINT references subjects(id) on delete cascade
+);
+
+-- the global mode has no subject, which counts as 0 so there is only one
+CREATE UNIQUE INDEX index_modes_on_subject_id ON modes((COALESCE(subject_id, 0)));
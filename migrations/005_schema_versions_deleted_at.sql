@@ -0,0 +1 @@
+ALTER TABLE schema_versions ADD COLUMN deleted_at TIMESTAMP WITHOUT TIME ZONE;
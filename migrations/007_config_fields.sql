@@ -0,0 +1,8 @@
+-- the rest of Confluent's config, the metadata and rule sets as json
+ALTER TABLE configs ADD COLUMN normalize BOOLEAN;
+ALTER TABLE configs ADD COLUMN alias CHARACTER VARYING;
+ALTER TABLE configs ADD COLUMN compatibility_group CHARACTER VARYING;
+ALTER TABLE configs ADD COLUMN default_metadata TEXT;
+ALTER TABLE configs ADD COLUMN override_metadata TEXT;
+ALTER TABLE configs ADD COLUMN default_rule_set TEXT;
+ALTER TABLE configs ADD COLUMN override_rule_set TEXT;
@@ -0,0 +1,37 @@
+CREATE TABLE subjects (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  deleted_at TIMESTAMP
+);
+
+CREATE UNIQUE INDEX index_subjects_on_name ON subjects(name);
+
+CREATE TABLE schemas (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  fingerprint TEXT NOT NULL,
+  json TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  deleted_at TIMESTAMP
+);
+
+CREATE UNIQUE INDEX index_schemas_on_fingerprint ON schemas(fingerprint);
+
+CREATE TABLE schema_versions (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  version INTEGER DEFAULT 1 NOT NULL,
+  subject_id INTEGER NOT NULL references subjects(id) on delete cascade,
+  schema_id INTEGER NOT NULL references schemas(id) on delete cascade
+);
+
+CREATE UNIQUE INDEX index_schema_versions_on_subject_id_and_version ON schema_versions(subject_id, version);
+
+CREATE TABLE configs (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  compatibility TEXT,
+  created_at TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP NOT NULL,
+  subject_id INTEGER references subjects(id) on delete cascade
+);
+
+CREATE UNIQUE INDEX index_configs_on_subject_id ON configs(subject_id);
@@ -0,0 +1 @@
+ALTER TABLE schemas ADD COLUMN schema_type TEXT NOT NULL DEFAULT 'AVRO';
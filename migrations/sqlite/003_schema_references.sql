@@ -0,0 +1,10 @@
+CREATE TABLE schema_references (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  schema_id INTEGER NOT NULL references schemas(id) on delete cascade,
+  name TEXT NOT NULL,
+  subject TEXT NOT NULL,
+  version INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX index_schema_references_on_schema_id_and_name ON schema_references(schema_id, name);
+CREATE INDEX index_schema_references_on_subject_and_version ON schema_references(subject, version);
@@ -0,0 +1,9 @@
+CREATE TABLE modes (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  mode TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP NOT NULL,
+  subject_id INTEGER references subjects(id) on delete cascade
+);
+
+CREATE UNIQUE INDEX index_modes_on_subject_id ON modes(IFNULL(subject_id, 0));
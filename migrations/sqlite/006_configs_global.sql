@@ -0,0 +1,5 @@
+-- the global config has no subject, so keep its latest row and treat nulls as equal like the index on modes
+DELETE FROM configs WHERE subject_id IS NULL AND id <> (SELECT max(id) FROM configs WHERE subject_id IS NULL);
+
+DROP INDEX index_configs_on_subject_id;
+CREATE UNIQUE INDEX index_configs_on_subject_id ON configs(IFNULL(subject_id, 0));
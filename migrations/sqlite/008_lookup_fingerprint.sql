@@ -0,0 +1,4 @@
+-- the fingerprint lookups with normalize compare, filled in at startup for rows written before it existed
+ALTER TABLE schemas ADD COLUMN lookup_fingerprint TEXT;
+
+CREATE INDEX index_schemas_on_lookup_fingerprint ON schemas(lookup_fingerprint);
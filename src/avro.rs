@@ -0,0 +1,418 @@
+use std::collections::{HashMap, HashSet};
+
+use apache_avro::Schema as AvroSchema;
+use apache_avro::schema::{Name, SchemaKind};
+use serde_json::{Map, Value};
+use crate::schema::{Incompatibility, InvalidSchema, ResolvedReference, SchemaProvider};
+
+const NAMED_TYPES: [&str; 4] = ["record", "error", "enum", "fixed"];
+const PRIMITIVE_TYPES: [&str; 8] = ["null", "boolean", "int", "long", "float", "double", "bytes", "string"];
+const COMPLEX_TYPES: [&str; 6] = ["record", "error", "enum", "fixed", "array", "map"];
+
+// the parsed schema and the document it was parsed from, apache_avro drops docs, aliases and defaults the normalized forms keep
+pub struct ParsedAvroSchema {
+    pub schema: AvroSchema,
+    document: Value
+}
+
+pub struct AvroSchemaProvider;
+
+impl SchemaProvider for AvroSchemaProvider {
+    type Schema = ParsedAvroSchema;
+
+    fn schema_type(&self) -> &'static str {
+        "AVRO"
+    }
+
+    fn parse(&self, schema: &str, references: &[ResolvedReference]) -> Result<ParsedAvroSchema, InvalidSchema> {
+        let document = parse_json(schema)?;
+
+        let parsed = if references.is_empty() {
+            AvroSchema::parse(&document)
+        } else {
+            AvroSchema::parse(&Inliner::new(references)?.inline(&document, ""))
+        };
+
+        Ok(ParsedAvroSchema { schema: parsed.map_err(|e| InvalidSchema(e.to_string()))?, document })
+    }
+
+    // docs don't change how data is read, so lookups leave them out
+    fn canonical_form(&self, schema: &ParsedAvroSchema) -> String {
+        Normalizer { docs: false }.normalize(&schema.document, "").to_string()
+    }
+
+    fn normalized_form(&self, schema: &ParsedAvroSchema) -> String {
+        Normalizer { docs: true }.normalize(&schema.document, "").to_string()
+    }
+
+    fn incompatibilities(&self, writers_schema: &ParsedAvroSchema, readers_schema: &ParsedAvroSchema) -> Vec<Incompatibility> {
+        AvroSchemaCompatibility::incompatibilities(&writers_schema.schema, &readers_schema.schema)
+    }
+}
+
+fn parse_json(schema: &str) -> Result<Value, InvalidSchema> {
+    serde_json::from_str(schema).map_err(|e| InvalidSchema(e.to_string()))
+}
+
+fn full_name(name: &str, namespace: &str) -> String {
+    if name.contains('.') || namespace.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", namespace, name)
+    }
+}
+
+fn namespace_of(full_name: &str) -> &str {
+    full_name.rsplit_once('.').map(|x| x.0).unwrap_or("")
+}
+
+// apache_avro only parses documents that define every named type they use,
+// so the first use of a referenced type is replaced by its definition
+struct Inliner {
+    definitions: HashMap<String, Value>,
+    defined: HashSet<String>
+}
+
+impl Inliner {
+    fn new(references: &[ResolvedReference]) -> Result<Inliner, InvalidSchema> {
+        let mut definitions = HashMap::new();
+
+        for reference in references {
+            let value = parse_json(&reference.schema)?;
+            let name = value.get("name").and_then(|x| x.as_str())
+                .ok_or(InvalidSchema(format!("reference {} is not a named type", reference.name)))?;
+            let namespace = value.get("namespace").and_then(|x| x.as_str()).unwrap_or("");
+
+            definitions.insert(full_name(name, namespace), value);
+        }
+
+        Ok(Inliner { definitions, defined: HashSet::new() })
+    }
+
+    fn inline(&mut self, value: &Value, namespace: &str) -> Value {
+        match value {
+            Value::String(name) => {
+                let name = full_name(name, namespace);
+                match self.definitions.get(&name).cloned() {
+                    Some(definition) if !self.defined.contains(&name) => self.inline(&definition, ""),
+                    _ => value.clone()
+                }
+            },
+            Value::Array(values) => Value::Array(values.iter().map(|x| self.inline(x, namespace)).collect()),
+            Value::Object(map) => Value::Object(self.inline_object(map, namespace)),
+            _ => value.clone()
+        }
+    }
+
+    fn inline_object(&mut self, map: &Map<String, Value>, namespace: &str) -> Map<String, Value> {
+        let namespace = match named_type(map, namespace) {
+            Some(name) => {
+                let namespace = namespace_of(&name).to_string();
+                self.defined.insert(name);
+                namespace
+            },
+            None => namespace.to_string()
+        };
+
+        let mut res = Map::new();
+
+        for (key, value) in map {
+            let value = match key.as_str() {
+                "type" | "items" | "values" => self.inline(value, &namespace),
+                "fields" => match value {
+                    Value::Array(fields) => Value::Array(fields.iter().map(|x| match x {
+                        Value::Object(field) => Value::Object(self.inline_field(field, &namespace)),
+                        _ => x.clone()
+                    }).collect()),
+                    _ => value.clone()
+                },
+                _ => value.clone()
+            };
+            res.insert(key.clone(), value);
+        }
+
+        res
+    }
+
+    fn inline_field(&mut self, field: &Map<String, Value>, namespace: &str) -> Map<String, Value> {
+        field.iter().map(|(key, value)| {
+            let value = if key == "type" { self.inline(value, namespace) } else { value.clone() };
+            (key.clone(), value)
+        }).collect()
+    }
+}
+
+// the same schema whatever its layout: keys sorted, names fully qualified, namespaces dropped and
+// types without attributes written as their name, referenced types stay names
+struct Normalizer {
+    docs: bool
+}
+
+impl Normalizer {
+    fn normalize(&self, value: &Value, namespace: &str) -> Value {
+        match value {
+            Value::String(name) if PRIMITIVE_TYPES.contains(&name.as_str()) => value.clone(),
+            Value::String(name) => Value::String(full_name(name, namespace)),
+            Value::Array(values) => Value::Array(values.iter().map(|x| self.normalize(x, namespace)).collect()),
+            Value::Object(map) => self.normalize_object(map, namespace),
+            _ => value.clone()
+        }
+    }
+
+    fn normalize_object(&self, map: &Map<String, Value>, namespace: &str) -> Value {
+        let name = named_type(map, namespace);
+        let namespace = name.as_deref().map(namespace_of).unwrap_or(namespace);
+
+        let mut keys: Vec<&String> = map.keys().filter(|x| *x != "namespace" && (self.docs || *x != "doc")).collect();
+        keys.sort();
+
+        if keys.len() == 1 && keys[0] == "type" {
+            return self.normalize(&map["type"], namespace)
+        }
+
+        let mut res = Map::new();
+
+        for key in keys {
+            let value = match key.as_str() {
+                "name" => name.clone().map(Value::String).unwrap_or(map[key].clone()),
+                "aliases" if name.is_some() => self.normalize(&map[key], namespace),
+                // the keyword of a complex type is no name to qualify
+                "type" if map[key].as_str().map(|x| COMPLEX_TYPES.contains(&x)).unwrap_or(false) => map[key].clone(),
+                "type" | "items" | "values" => self.normalize(&map[key], namespace),
+                "fields" => match &map[key] {
+                    Value::Array(fields) => Value::Array(fields.iter().map(|x| match x {
+                        Value::Object(field) => self.normalize_field(field, namespace),
+                        _ => x.clone()
+                    }).collect()),
+                    value => value.clone()
+                },
+                _ => sorted(&map[key])
+            };
+            res.insert(key.clone(), value);
+        }
+
+        Value::Object(res)
+    }
+
+    fn normalize_field(&self, field: &Map<String, Value>, namespace: &str) -> Value {
+        let mut keys: Vec<&String> = field.keys().filter(|x| self.docs || *x != "doc").collect();
+        keys.sort();
+
+        let mut res = Map::new();
+        for key in keys {
+            let value = if key == "type" { self.normalize(&field[key], namespace) } else { sorted(&field[key]) };
+            res.insert(key.clone(), value);
+        }
+
+        Value::Object(res)
+    }
+}
+
+// defaults and custom attributes are plain json, only their keys are sorted
+fn sorted(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            Value::Object(keys.into_iter().map(|key| (key.clone(), sorted(&map[key]))).collect())
+        },
+        Value::Array(values) => Value::Array(values.iter().map(sorted).collect()),
+        _ => value.clone()
+    }
+}
+
+// the full name of a record, enum or fixed definition
+fn named_type(map: &Map<String, Value>, namespace: &str) -> Option<String> {
+    let is_named = map.get("type").and_then(|x| x.as_str()).map(|x| NAMED_TYPES.contains(&x)).unwrap_or(false);
+
+    match map.get("name").and_then(|x| x.as_str()) {
+        Some(name) if is_named => {
+            let namespace = map.get("namespace").and_then(|x| x.as_str()).unwrap_or(namespace);
+            Some(full_name(name, namespace))
+        },
+        _ => None
+    }
+}
+
+pub struct AvroSchemaCompatibility;
+
+impl AvroSchemaCompatibility {
+    pub fn incompatibilities(writers_schema: &AvroSchema, readers_schema: &AvroSchema) -> Vec<Incompatibility> {
+        let mut checker = Checker {
+            writers_names: named_schemas(writers_schema),
+            readers_names: named_schemas(readers_schema),
+            in_progress: HashSet::new(),
+            res: vec![]
+        };
+
+        checker.check("", writers_schema, readers_schema);
+        checker.res
+    }
+}
+
+fn named_schemas(schema: &AvroSchema) -> HashMap<String, &AvroSchema> {
+    let mut res = HashMap::new();
+    collect_named_schemas(schema, &mut res);
+    res
+}
+
+fn collect_named_schemas<'a>(schema: &'a AvroSchema, res: &mut HashMap<String, &'a AvroSchema>) {
+    match schema {
+        AvroSchema::Record { name, fields, .. } => {
+            res.insert(name.fullname(None), schema);
+            fields.iter().for_each(|x| collect_named_schemas(&x.schema, res));
+        },
+        AvroSchema::Enum { name, .. } | AvroSchema::Fixed { name, .. } => {
+            res.insert(name.fullname(None), schema);
+        },
+        AvroSchema::Array(items) => collect_named_schemas(items, res),
+        AvroSchema::Map(values) => collect_named_schemas(values, res),
+        AvroSchema::Union(union) => union.variants().iter().for_each(|x| collect_named_schemas(x, res)),
+        _ => ()
+    }
+}
+
+fn type_name(schema: &AvroSchema) -> String {
+    match schema {
+        AvroSchema::Record { name, .. } | AvroSchema::Enum { name, .. } | AvroSchema::Fixed { name, .. } | AvroSchema::Ref { name } => name.fullname(None),
+        AvroSchema::Union(union) => format!("[{}]", union.variants().iter().map(type_name).collect::<Vec<String>>().join(", ")),
+        _ => format!("{:?}", SchemaKind::from(schema)).to_lowercase()
+    }
+}
+
+// the schema resolution rules of the Avro specification, collecting every rule a reader breaks
+struct Checker<'a> {
+    writers_names: HashMap<String, &'a AvroSchema>,
+    readers_names: HashMap<String, &'a AvroSchema>,
+    in_progress: HashSet<(String, String)>,
+    res: Vec<Incompatibility>
+}
+
+impl <'a> Checker<'a> {
+    // refs left by the parser name either the full name or only the last part of it
+    fn resolve(names: &HashMap<String, &'a AvroSchema>, schema: &'a AvroSchema) -> &'a AvroSchema {
+        match schema {
+            AvroSchema::Ref { name } => names.get(&name.fullname(None))
+                .or_else(|| names.iter().find(|(x, _)| x.rsplit('.').next() == Some(name.name.as_str())).map(|x| x.1))
+                .copied()
+                .unwrap_or(schema),
+            _ => schema
+        }
+    }
+
+    fn report(&mut self, path: &str, reason: &str, writer: &AvroSchema, reader: &AvroSchema) {
+        let path = if path.is_empty() { "/" } else { path };
+        self.res.push(Incompatibility::new(path, reason, &type_name(reader), &type_name(writer)));
+    }
+
+    // whether the reader can read the writer, without reporting anything
+    fn probe(&mut self, path: &str, writer: &'a AvroSchema, reader: &'a AvroSchema) -> bool {
+        let len = self.res.len();
+        self.check(path, writer, reader);
+        let res = self.res.len() == len;
+        self.res.truncate(len);
+        res
+    }
+
+    fn check(&mut self, path: &str, writer: &'a AvroSchema, reader: &'a AvroSchema) {
+        let writer = Self::resolve(&self.writers_names, writer);
+        let reader = Self::resolve(&self.readers_names, reader);
+
+        match (writer, reader) {
+            (AvroSchema::Union(writers_union), AvroSchema::Union(readers_union)) => {
+                for (i, branch) in writers_union.variants().iter().enumerate() {
+                    if !readers_union.variants().iter().any(|x| self.probe(path, branch, x)) {
+                        self.report(&format!("{}/{}", path, i), "the reader's union has no branch for the writer's type", branch, reader);
+                    }
+                }
+            },
+            (_, AvroSchema::Union(readers_union)) => {
+                if !readers_union.variants().iter().any(|x| self.probe(path, writer, x)) {
+                    self.report(path, "the reader's union has no branch for the writer's type", writer, reader);
+                }
+            },
+            (AvroSchema::Union(writers_union), _) => {
+                for (i, branch) in writers_union.variants().iter().enumerate() {
+                    self.check(&format!("{}/{}", path, i), branch, reader);
+                }
+            },
+            (AvroSchema::Record { name: writers_name, fields: writers_fields, .. }, AvroSchema::Record { name: readers_name, fields: readers_fields, .. }) => {
+                if !self.names_match(path, writers_name, readers_name, writer, reader) {
+                    return
+                }
+
+                let key = (writers_name.fullname(None), readers_name.fullname(None));
+                if !self.in_progress.insert(key.clone()) {
+                    return
+                }
+
+                for readers_field in readers_fields {
+                    let field_path = format!("{}/{}", path, readers_field.name);
+                    match writers_fields.iter().find(|x| x.name == readers_field.name) {
+                        Some(writers_field) => self.check(&field_path, &writers_field.schema, &readers_field.schema),
+                        None if readers_field.default.is_none() => {
+                            let reader_type = type_name(&readers_field.schema);
+                            self.res.push(Incompatibility::new(&field_path, "the reader's field has no default value and is missing in the writer", &reader_type, "missing"));
+                        },
+                        None => ()
+                    }
+                }
+
+                self.in_progress.remove(&key);
+            },
+            (AvroSchema::Enum { name: writers_name, symbols: writers_symbols, .. }, AvroSchema::Enum { name: readers_name, symbols: readers_symbols, .. }) => {
+                if self.names_match(path, writers_name, readers_name, writer, reader) {
+                    let missing: Vec<&str> = writers_symbols.iter().filter(|x| !readers_symbols.contains(x)).map(|x| x.as_str()).collect();
+                    if !missing.is_empty() {
+                        self.report(path, &format!("the reader's enum is missing the symbols {}", missing.join(", ")), writer, reader);
+                    }
+                }
+            },
+            (AvroSchema::Fixed { name: writers_name, size: writers_size, .. }, AvroSchema::Fixed { name: readers_name, size: readers_size, .. }) => {
+                if self.names_match(path, writers_name, readers_name, writer, reader) && writers_size != readers_size {
+                    self.report(path, &format!("the fixed size changed from {} to {}", writers_size, readers_size), writer, reader);
+                }
+            },
+            (AvroSchema::Array(writers_items), AvroSchema::Array(readers_items)) => self.check(&format!("{}/items", path), writers_items, readers_items),
+            (AvroSchema::Map(writers_values), AvroSchema::Map(readers_values)) => self.check(&format!("{}/values", path), writers_values, readers_values),
+            _ => {
+                if !Self::promotes(writer, reader) {
+                    self.report(path, "the reader's type does not match the writer's type", writer, reader);
+                }
+            }
+        }
+    }
+
+    fn names_match(&mut self, path: &str, writers_name: &Name, readers_name: &Name, writer: &AvroSchema, reader: &AvroSchema) -> bool {
+        let res = writers_name.fullname(None) == readers_name.fullname(None);
+        if !res {
+            self.report(path, "the reader's type name does not match the writer's type name", writer, reader);
+        }
+        res
+    }
+
+    // logical types are read as the type they annotate
+    fn base_kind(schema: &AvroSchema) -> SchemaKind {
+        match schema {
+            AvroSchema::Date | AvroSchema::TimeMillis => SchemaKind::Int,
+            AvroSchema::TimeMicros | AvroSchema::TimestampMillis | AvroSchema::TimestampMicros => SchemaKind::Long,
+            AvroSchema::Uuid => SchemaKind::String,
+            AvroSchema::Decimal { inner, .. } => SchemaKind::from(inner.as_ref()),
+            AvroSchema::Duration => SchemaKind::Fixed,
+            _ => SchemaKind::from(schema)
+        }
+    }
+
+    fn promotes(writer: &AvroSchema, reader: &AvroSchema) -> bool {
+        let writers_kind = Self::base_kind(writer);
+        let readers_kind = Self::base_kind(reader);
+
+        match (writers_kind, readers_kind) {
+            (SchemaKind::Int, SchemaKind::Long | SchemaKind::Float | SchemaKind::Double) => true,
+            (SchemaKind::Long, SchemaKind::Float | SchemaKind::Double) => true,
+            (SchemaKind::Float, SchemaKind::Double) => true,
+            (SchemaKind::String, SchemaKind::Bytes) | (SchemaKind::Bytes, SchemaKind::String) => true,
+            _ => writers_kind == readers_kind
+        }
+    }
+}
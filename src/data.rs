@@ -1,26 +1,79 @@
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::str::FromStr;
 use sqlx::FromRow;
 use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
 
-#[derive(FromRow, Serialize)]
-pub struct Schema {
-    pub fingerprint: String
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaPayload {
+    pub schema: String,
+    #[serde(default, skip_serializing_if = "SchemaType::is_avro")]
+    pub schema_type: SchemaType,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<SchemaReference>,
+    // only accepted in IMPORT mode, to register a schema under the id and version it has elsewhere
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<i32>
 }
 
-#[derive(FromRow, Deserialize, Serialize)]
-pub struct SchemaPayload {
-    pub schema: String
+#[derive(FromRow, Deserialize, Serialize, Clone)]
+pub struct SchemaReference {
+    pub name: String,
+    pub subject: String,
+    pub version: i32
 }
 
-#[derive(FromRow, Serialize)]
+#[derive(FromRow)]
+pub struct SubjectSchema {
+    pub subject: String,
+    pub version: i32,
+    pub id: i64,
+    pub schema: String,
+    #[sqlx(try_from = "String")]
+    pub schema_type: SchemaType
+}
+
+// the fields and their order follow Confluent's Schema entity, which omits the default AVRO type and empty references
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct FindBySchemaResponse {
-    pub name: String,
+    pub subject: String,
     pub version: i32,
     pub id: i64,
+    #[serde(skip_serializing_if = "SchemaType::is_avro")]
+    pub schema_type: SchemaType,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<SchemaReference>,
     pub schema: String
 }
 
+// Confluent's SchemaString, the body of GET /schemas/ids/:id
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaByIdResponse {
+    #[serde(skip_serializing_if = "SchemaType::is_avro")]
+    pub schema_type: SchemaType,
+    pub schema: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<SchemaReference>
+}
+
+impl From<SchemaPayload> for SchemaByIdResponse {
+    fn from(value: SchemaPayload) -> Self {
+        SchemaByIdResponse { schema_type: value.schema_type, schema: value.schema, references: value.references }
+    }
+}
+
+#[derive(FromRow, Serialize)]
+pub struct SubjectVersion {
+    pub subject: String,
+    pub version: i32
+}
+
 #[derive(FromRow, Serialize)]
 pub struct RegisterSchemaResponse {
     pub id: i64
@@ -37,7 +90,9 @@ pub struct Subject {
 pub struct VersionedSchema {
     pub version: i32,
     pub id: i64,
-    pub schema: String
+    pub schema: String,
+    #[sqlx(try_from = "String")]
+    pub schema_type: SchemaType
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -54,17 +109,133 @@ impl FromStr for VersionId {
             return Ok(VersionId::Latest)
         }
 
-        let version = s.parse::<i32>().or(Err(()))?;
-
-        Ok(VersionId::Version(version))
+        // clients use -1 as an alias of latest
+        match s.parse::<i32>().or(Err(()))? {
+            -1 => Ok(VersionId::Latest),
+            version if version > 0 => Ok(VersionId::Version(version)),
+            _ => Err(())
+        }
     }
 }
 
+#[derive(Deserialize)]
+pub struct SchemaParams {
+    #[serde(default)]
+    pub verbose: bool,
+    #[serde(default)]
+    pub normalize: bool
+}
+
+#[derive(Deserialize)]
+pub struct LookupParams {
+    #[serde(default)]
+    pub deleted: bool
+}
+
+// a negative or zero limit lists everything
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SubjectsParams {
+    #[serde(default)]
+    pub subject_prefix: String,
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub offset: i64,
+    #[serde(default)]
+    pub limit: i64
+}
+
+#[derive(Deserialize)]
+pub struct VersionsParams {
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub offset: i64,
+    #[serde(default)]
+    pub limit: i64
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemasParams {
+    #[serde(default)]
+    pub subject_prefix: String,
+    #[serde(default)]
+    pub latest_only: bool,
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub offset: i64,
+    #[serde(default)]
+    pub limit: i64
+}
+
+// of the config and mode reads
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigParams {
+    #[serde(default)]
+    pub default_to_global: bool
+}
+
+#[derive(Deserialize)]
+pub struct DeleteParams {
+    #[serde(default)]
+    pub permanent: bool
+}
+
+#[derive(Serialize)]
+pub struct CompatibilityCheckResponse {
+    pub compatibility: Compatibility,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Vec<String>>
+}
+
+#[derive(Serialize)]
+pub struct CompatibilityResult {
+    pub is_compatible: bool,
+    pub messages: Vec<String>
+}
+
 #[derive(FromRow)]
 pub struct MaxVersion {
     pub max_version: Option<i32>
 }
 
+// what schemas are stored under and what lookups with normalize compare, see ParsedSchema
+#[derive(Clone, PartialEq)]
+pub struct Fingerprints {
+    pub fingerprint: String,
+    pub lookup_fingerprint: String
+}
+
+// what Service::schema_rehash did, the schemas that no longer parse keep their fingerprints
+#[derive(Default)]
+pub struct RehashSummary {
+    pub updated: usize,
+    pub unparsed: Vec<i64>
+}
+
+// what Repository::schema_import found, it only stores the schema when the id and version are free or already hold it
+pub enum SchemaImport {
+    Imported,
+    // the id holds another schema
+    IdTaken,
+    // the subject version holds another schema
+    VersionTaken,
+    // the schema is stored under this other id
+    OtherId(i64)
+}
+
+// the fingerprints a schema is stored under, rows written by an older release have no lookup fingerprint
+#[derive(FromRow)]
+pub struct StoredFingerprints {
+    pub id: i64,
+    pub fingerprint: String,
+    pub lookup_fingerprint: Option<String>
+}
+
 impl Eq for VersionedSchema {}
 
 impl PartialEq for VersionedSchema {
@@ -75,7 +246,7 @@ impl PartialEq for VersionedSchema {
 
 impl PartialOrd for VersionedSchema {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.version.partial_cmp(&other.version)
+        Some(self.cmp(other))
     }
 }
 
@@ -86,7 +257,34 @@ impl Ord for VersionedSchema {
 
 }
 
-#[derive(Serialize, Deserialize)]
+// names the schema provider, e.g. AVRO, PROTOBUF or JSON
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(transparent)]
+pub struct SchemaType(pub String);
+
+impl SchemaType {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn is_avro(&self) -> bool {
+        self.0 == "AVRO"
+    }
+}
+
+impl Default for SchemaType {
+    fn default() -> Self {
+        SchemaType(String::from("AVRO"))
+    }
+}
+
+impl From<String> for SchemaType {
+    fn from(value: String) -> Self {
+        SchemaType(value)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Compatibility {
     Backward,
@@ -113,23 +311,221 @@ impl Compatibility {
     }
 }
 
-//TODO: is the right way for sqlx to decode?
-impl From<Option<String>> for Compatibility {
-    fn from(value: Option<String>) -> Self {
-        match value.as_deref() {
-            Some("BACKWARD") => Compatibility::Backward,
-            Some("BACKWARD_TRANSITIVE") => Compatibility::BackwardTransitive,
-            Some("FORWARD") => Compatibility::Forward,
-            Some("FORWARD_TRANSITIVE") => Compatibility::ForwardTransitive,
-            Some("FULL") => Compatibility::Full,
-            Some("FULL_TRANSITIVE") => Compatibility::FullTransitive,
-            _ => Compatibility::None
+// clients send the level in any case, e.g. backward_transitive
+impl FromStr for Compatibility {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "BACKWARD" => Ok(Compatibility::Backward),
+            "BACKWARD_TRANSITIVE" => Ok(Compatibility::BackwardTransitive),
+            "FORWARD" => Ok(Compatibility::Forward),
+            "FORWARD_TRANSITIVE" => Ok(Compatibility::ForwardTransitive),
+            "FULL" => Ok(Compatibility::Full),
+            "FULL_TRANSITIVE" => Ok(Compatibility::FullTransitive),
+            "NONE" => Ok(Compatibility::None),
+            _ => Err(())
         }
     }
 }
 
-#[derive(FromRow, Serialize, Deserialize)]
-pub struct SchemaCompatibility {
-    pub compatibility: Compatibility
+// the metadata and rules of Confluent's data contracts, which are kept and returned but not enforced
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct Metadata {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sensitive: Vec<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleSet {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub migration_rules: Vec<Map<String, Value>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub domain_rules: Vec<Map<String, Value>>
 }
 
+// a subject or the global config, unset fields fall back to the global config and then to `Config::defaults`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<bool>,
+    // older log records name it `compatibility`
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "compatibility")]
+    pub compatibility_level: Option<Compatibility>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compatibility_group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_metadata: Option<Metadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub override_metadata: Option<Metadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_rule_set: Option<RuleSet>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub override_rule_set: Option<RuleSet>
+}
+
+impl Config {
+    pub fn defaults() -> Config {
+        Config { compatibility_level: Some(Compatibility::Backward), ..Config::default() }
+    }
+
+    // the fields set here, then the ones set on `fallback`, except the alias which only ever belongs to its own subject
+    pub fn or(self, fallback: Config) -> Config {
+        Config {
+            alias: self.alias,
+            normalize: self.normalize.or(fallback.normalize),
+            compatibility_level: self.compatibility_level.or(fallback.compatibility_level),
+            compatibility_group: self.compatibility_group.or(fallback.compatibility_group),
+            default_metadata: self.default_metadata.or(fallback.default_metadata),
+            override_metadata: self.override_metadata.or(fallback.override_metadata),
+            default_rule_set: self.default_rule_set.or(fallback.default_rule_set),
+            override_rule_set: self.override_rule_set.or(fallback.override_rule_set)
+        }
+    }
+
+    pub fn compatibility(&self) -> Compatibility {
+        self.compatibility_level.unwrap_or(Compatibility::Backward)
+    }
+}
+
+// the body of PUT /config, which names the level `compatibility` unlike the config it updates
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "compatibilityLevel")]
+    pub compatibility: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compatibility_group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_metadata: Option<Metadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub override_metadata: Option<Metadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_rule_set: Option<RuleSet>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub override_rule_set: Option<RuleSet>
+}
+
+impl From<Config> for ConfigUpdate {
+    fn from(value: Config) -> Self {
+        ConfigUpdate {
+            alias: value.alias,
+            normalize: value.normalize,
+            compatibility: value.compatibility_level.map(|x| x.as_str().to_string()),
+            compatibility_group: value.compatibility_group,
+            default_metadata: value.default_metadata,
+            override_metadata: value.override_metadata,
+            default_rule_set: value.default_rule_set,
+            override_rule_set: value.override_rule_set
+        }
+    }
+}
+
+// a row of configs, whose metadata and rule sets are json text
+#[derive(FromRow)]
+pub struct ConfigRecord {
+    pub compatibility: Option<String>,
+    pub normalize: Option<bool>,
+    pub alias: Option<String>,
+    pub compatibility_group: Option<String>,
+    pub default_metadata: Option<String>,
+    pub override_metadata: Option<String>,
+    pub default_rule_set: Option<String>,
+    pub override_rule_set: Option<String>
+}
+
+impl From<ConfigRecord> for Config {
+    fn from(value: ConfigRecord) -> Self {
+        fn parse<T : serde::de::DeserializeOwned>(json: Option<String>) -> Option<T> {
+            json.and_then(|x| serde_json::from_str(&x).ok())
+        }
+
+        Config {
+            alias: value.alias,
+            normalize: value.normalize,
+            compatibility_level: value.compatibility.and_then(|x| x.parse().ok()),
+            compatibility_group: value.compatibility_group,
+            default_metadata: parse(value.default_metadata),
+            override_metadata: parse(value.override_metadata),
+            default_rule_set: parse(value.default_rule_set),
+            override_rule_set: parse(value.override_rule_set)
+        }
+    }
+}
+
+impl From<&Config> for ConfigRecord {
+    fn from(value: &Config) -> Self {
+        fn json<T : Serialize>(value: &Option<T>) -> Option<String> {
+            value.as_ref().and_then(|x| serde_json::to_string(x).ok())
+        }
+
+        ConfigRecord {
+            compatibility: value.compatibility_level.map(|x| x.as_str().to_string()),
+            normalize: value.normalize,
+            alias: value.alias.clone(),
+            compatibility_group: value.compatibility_group.clone(),
+            default_metadata: json(&value.default_metadata),
+            override_metadata: json(&value.override_metadata),
+            default_rule_set: json(&value.default_rule_set),
+            override_rule_set: json(&value.override_rule_set)
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    #[serde(rename = "READWRITE")]
+    ReadWrite,
+    #[serde(rename = "READONLY")]
+    ReadOnly,
+    #[serde(rename = "IMPORT")]
+    Import,
+    // the modes of newer Confluent registries, which read and write like the modes they extend
+    #[serde(rename = "READONLY_OVERRIDE")]
+    ReadOnlyOverride,
+    #[serde(rename = "READWRITE_OVERRIDE")]
+    ReadWriteOverride
+}
+
+impl Mode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mode::ReadWrite => "READWRITE",
+            Mode::ReadOnly => "READONLY",
+            Mode::Import => "IMPORT",
+            Mode::ReadOnlyOverride => "READONLY_OVERRIDE",
+            Mode::ReadWriteOverride => "READWRITE_OVERRIDE"
+        }
+    }
+}
+
+impl From<String> for Mode {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "READONLY" => Mode::ReadOnly,
+            "IMPORT" => Mode::Import,
+            "READONLY_OVERRIDE" => Mode::ReadOnlyOverride,
+            "READWRITE_OVERRIDE" => Mode::ReadWriteOverride,
+            _ => Mode::ReadWrite
+        }
+    }
+}
+
+#[derive(FromRow, Serialize, Deserialize)]
+pub struct SchemaMode {
+    #[sqlx(try_from = "String")]
+    pub mode: Mode
+}
@@ -1,20 +1,39 @@
 use axum::response::{IntoResponse, Response};
 use hyper::StatusCode;
 use sqlx::error::{Error as SqlxError};
-use apache_avro::{Error as AvroError};
 use axum::Json;
 use serde::Serialize;
 use crate::data::VersionId;
+use crate::schema::InvalidSchema;
 
 #[derive(Debug)]
 pub enum AppError {
     DatabaseError(SqlxError),
-    AvroError(AvroError),
+    Timeout,
+    InvalidSchema(InvalidSchema),
     SubjectNotFound(String),
-    SchemaNotFound(String, VersionId),
-    IncompatibleSchema,
-    InvalidVersion,
-    JsonError
+    VersionNotFound(String, VersionId),
+    SchemaNotFound,
+    SchemaIdNotFound(i64),
+    SubjectConfigNotFound(String),
+    SubjectModeNotFound(String),
+    // the subject and why the schema is incompatible, the reasons are only listed when non-empty
+    IncompatibleSchema(String, Vec<String>),
+    ConcurrentModification,
+    ReferenceExists(String, i32),
+    // a permanent delete of a subject or version that was not soft deleted first
+    SubjectNotSoftDeleted(String),
+    VersionNotSoftDeleted(String, i32),
+    InvalidVersion(String),
+    InvalidCompatibilityLevel,
+    InvalidMode,
+    OperationNotPermitted(String),
+    // the id the schema is registered under and the id that was asked for
+    IdDoesNotMatch(i64, i64),
+    // the line of an imported record and why it was rejected
+    ImportFailed(usize, Box<AppError>),
+    // a request axum could not extract, reported with its own status
+    InvalidRequest(StatusCode, String)
 }
 
 #[derive(Serialize)]
@@ -24,29 +43,83 @@ pub struct ApiError {
 }
 
 impl From<SqlxError> for AppError {
-    fn from(value: SqlxError) -> Self { AppError::DatabaseError(value) }
+    fn from(value: SqlxError) -> Self {
+        match value {
+            SqlxError::PoolTimedOut => AppError::Timeout,
+            _ => AppError::DatabaseError(value)
+        }
+    }
 }
 
-impl From<AvroError> for AppError {
-    fn from(value: AvroError) -> Self { AppError::AvroError(value) }
+impl From<InvalidSchema> for AppError {
+    fn from(value: InvalidSchema) -> Self { AppError::InvalidSchema(value) }
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
+impl AppError {
+    pub fn parts(self) -> (StatusCode, u32, String) {
         match self {
-            AppError::DatabaseError(error) =>
-                (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiError { error_code: 50001, message: error.to_string() })).into_response(),
-            AppError::AvroError(error) =>
-                (StatusCode::UNPROCESSABLE_ENTITY, Json(ApiError { error_code: 42201, message: error.to_string() })).into_response(),
-            AppError::SubjectNotFound(_) =>
-                (StatusCode::NOT_FOUND, Json(ApiError { error_code: 40401, message: String::from("subject was not found") })).into_response(),
-            AppError::SchemaNotFound(_, _) =>
-                (StatusCode::NOT_FOUND, Json(ApiError { error_code: 40402, message: String::from("schema was not found") })).into_response(),
-            AppError::InvalidVersion =>
-                (StatusCode::NOT_FOUND, Json(ApiError { error_code: 40402, message: String::from("version not found")})).into_response(),
-            AppError::IncompatibleSchema =>
-                (StatusCode::CONFLICT, Json(ApiError { error_code: 409, message: String::from("schema incompatible")})).into_response(),
-            AppError::JsonError => (StatusCode::BAD_REQUEST).into_response()
+            AppError::DatabaseError(error) => {
+                // the driver's message stays in the logs, clients only learn that the backend failed
+                eprintln!("database error: {}", error);
+                (StatusCode::INTERNAL_SERVER_ERROR, 50001, String::from("Error in the backend datastore"))
+            },
+            AppError::Timeout =>
+                (StatusCode::INTERNAL_SERVER_ERROR, 50002, String::from("Operation timed out")),
+            AppError::InvalidSchema(error) =>
+                (StatusCode::UNPROCESSABLE_ENTITY, 42201, format!("Invalid schema: {}", error.0)),
+            AppError::SubjectNotFound(subject) =>
+                (StatusCode::NOT_FOUND, 40401, format!("Subject '{}' not found.", subject)),
+            AppError::VersionNotFound(_, version_id) =>
+                (StatusCode::NOT_FOUND, 40402, match version_id {
+                    VersionId::Latest => String::from("Version latest not found."),
+                    VersionId::Version(version) => format!("Version {} not found.", version)
+                }),
+            AppError::SchemaNotFound =>
+                (StatusCode::NOT_FOUND, 40403, String::from("Schema not found")),
+            AppError::SchemaIdNotFound(id) =>
+                (StatusCode::NOT_FOUND, 40403, format!("Schema {} not found", id)),
+            AppError::SubjectConfigNotFound(subject) =>
+                (StatusCode::NOT_FOUND, 40408, format!("Subject '{}' does not have subject-level compatibility configured", subject)),
+            AppError::SubjectModeNotFound(subject) =>
+                (StatusCode::NOT_FOUND, 40409, format!("Subject '{}' does not have subject-level mode configured", subject)),
+            AppError::IncompatibleSchema(subject, messages) => {
+                let message = format!("Schema being registered is incompatible with an earlier schema for subject \"{}\"", subject);
+                match messages.is_empty() {
+                    true => (StatusCode::CONFLICT, 409, message),
+                    false => (StatusCode::CONFLICT, 409, format!("{}, details: [{}]", message, messages.join("; ")))
+                }
+            },
+            AppError::ConcurrentModification =>
+                (StatusCode::CONFLICT, 40901, String::from("subject was modified concurrently, retry the request")),
+            AppError::ReferenceExists(subject, version) =>
+                (StatusCode::UNPROCESSABLE_ENTITY, 42206, format!("One or more references exist to version {} of subject '{}'", version, subject)),
+            AppError::SubjectNotSoftDeleted(subject) =>
+                (StatusCode::NOT_FOUND, 40405, format!("Subject '{}' was not deleted first before being permanently deleted", subject)),
+            AppError::VersionNotSoftDeleted(subject, version) =>
+                (StatusCode::NOT_FOUND, 40407, format!("Subject '{}' Version {} was not deleted first before being permanently deleted", subject, version)),
+            AppError::InvalidVersion(version) =>
+                (StatusCode::UNPROCESSABLE_ENTITY, 42202, format!("The specified version '{}' is not a valid version id. Allowed values are between [1, 2^31-1] and the string \"latest\"", version)),
+            AppError::InvalidCompatibilityLevel =>
+                (StatusCode::UNPROCESSABLE_ENTITY, 42203, String::from("Invalid compatibility level. Valid values are none, backward, forward, full, backward_transitive, forward_transitive, and full_transitive")),
+            AppError::InvalidMode =>
+                (StatusCode::UNPROCESSABLE_ENTITY, 42204, String::from("Invalid mode. Valid values are READWRITE, READONLY, IMPORT, READONLY_OVERRIDE and READWRITE_OVERRIDE")),
+            AppError::OperationNotPermitted(message) =>
+                (StatusCode::UNPROCESSABLE_ENTITY, 42205, message),
+            AppError::IdDoesNotMatch(existing, id) =>
+                (StatusCode::UNPROCESSABLE_ENTITY, 42207, format!("Schema already registered with id {} instead of input id {}", existing, id)),
+            AppError::ImportFailed(line, error) => {
+                let (status, error_code, message) = error.parts();
+                (status, error_code, format!("Record on line {}: {}", line, message))
+            },
+            AppError::InvalidRequest(status, message) =>
+                (status, status.as_u16() as u32, message)
         }
     }
-}
\ No newline at end of file
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let (status, error_code, message) = self.parts();
+        (status, Json(ApiError { error_code, message })).into_response()
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::{HashMap, HashSet};
+
+use jsonschema::JSONSchema;
+use serde_json::{json, Map, Value};
+use crate::schema::{Incompatibility, InvalidSchema, ResolvedReference, SchemaProvider};
+
+const ANNOTATIONS: [&str; 10] = ["$schema", "$id", "$comment", "title", "description", "default", "examples", "definitions", "$defs", "deprecated"];
+const LOWER_BOUNDS: [&str; 5] = ["minimum", "exclusiveMinimum", "minLength", "minItems", "minProperties"];
+const UPPER_BOUNDS: [&str; 5] = ["maximum", "exclusiveMaximum", "maxLength", "maxItems", "maxProperties"];
+const MAX_REF_DEPTH: usize = 32;
+// relative `$ref`s are resolved against this base when the schema has no `$id`
+const DEFAULT_BASE_URI: &str = "json-schema:///";
+
+pub struct JsonSchema {
+    pub value: Value,
+    // referenced documents by reference name
+    pub references: HashMap<String, Value>
+}
+
+impl JsonSchema {
+    pub fn parse_str(input: &str, references: &[ResolvedReference]) -> Result<JsonSchema, InvalidSchema> {
+        let value: Value = serde_json::from_str(input).map_err(|e| InvalidSchema(e.to_string()))?;
+
+        if !value.is_object() && !value.is_boolean() {
+            return Err(InvalidSchema(String::from("a JSON schema must be an object or a boolean")))
+        }
+
+        let mut documents = HashMap::new();
+        for reference in references {
+            let document: Value = serde_json::from_str(&reference.schema).map_err(|e| InvalidSchema(format!("reference {}: {}", reference.name, e)))?;
+            documents.insert(reference.name.clone(), document);
+        }
+
+        let schema = JsonSchema { value, references: documents };
+
+        // the validator never fetches documents, so every external `$ref` has to be one of the references, which
+        // SchemaProviders::parse checks through JsonSchemaProvider::references
+        // compiling checks the document against the meta-schema of its draft
+        let mut options = JSONSchema::options();
+        for (name, document) in &schema.references {
+            options.with_document(name.clone(), document.clone());
+            options.with_document(format!("{}{}", DEFAULT_BASE_URI, name), document.clone());
+        }
+        options.compile(&schema.value).map_err(|e| InvalidSchema(e.to_string()))?;
+
+        Ok(schema)
+    }
+
+    pub fn canonical_form(&self) -> String {
+        normalize(&self.value).to_string()
+    }
+
+    // `$ref`s that point outside of this document
+    pub fn external_refs(&self) -> Vec<String> {
+        let mut res = vec![];
+        collect_external_refs(&self.value, &mut res);
+        res
+    }
+}
+
+fn collect_external_refs(value: &Value, res: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::String(reference)) = map.get("$ref") {
+                if !reference.starts_with('#') && !res.contains(reference) {
+                    res.push(reference.clone());
+                }
+            }
+            map.values().for_each(|x| collect_external_refs(x, res));
+        },
+        Value::Array(values) => values.iter().for_each(|x| collect_external_refs(x, res)),
+        _ => ()
+    }
+}
+
+fn normalize(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+
+            let mut res = Map::new();
+            for key in keys {
+                res.insert(key.clone(), normalize(&map[key]));
+            }
+            Value::Object(res)
+        },
+        Value::Array(values) => Value::Array(values.iter().map(normalize).collect()),
+        _ => value.clone()
+    }
+}
+
+fn is_empty_schema(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Object(map) => map.keys().all(|x| ANNOTATIONS.contains(&x.as_str())),
+        _ => false
+    }
+}
+
+fn types(map: &Map<String, Value>) -> Option<Vec<&str>> {
+    match map.get("type") {
+        Some(Value::String(x)) => Some(vec![x.as_str()]),
+        Some(Value::Array(xs)) => Some(xs.iter().filter_map(|x| x.as_str()).collect()),
+        _ => None
+    }
+}
+
+fn union(map: &Map<String, Value>) -> Option<(&'static str, &Vec<Value>)> {
+    ["anyOf", "oneOf"].into_iter().find_map(|keyword| map.get(keyword).and_then(|x| x.as_array()).map(|x| (keyword, x)))
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Bool(b) => b.to_string(),
+        Value::Object(map) => match (types(map), union(map)) {
+            (Some(types), _) => types.join(", "),
+            (None, Some((keyword, _))) => keyword.to_string(),
+            (None, None) if map.contains_key("enum") => String::from("enum"),
+            (None, None) if map.contains_key("const") => String::from("const"),
+            _ => String::from("any")
+        },
+        _ => String::from("invalid")
+    }
+}
+
+// since draft 2019-09 the keywords next to a `$ref` apply along with the schema it points to, so such a schema is
+// compared as the allOf of the two, while a `$ref` with only annotations next to it stays as it is
+fn expand_refs(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut res: Map<String, Value> = map.iter().map(|(key, value)| (key.clone(), expand_refs(value))).collect();
+
+            if res.contains_key("$ref") && res.keys().any(|x| x != "$ref" && !ANNOTATIONS.contains(&x.as_str())) {
+                let reference = res.remove("$ref").unwrap_or_default();
+                let mut target = Map::new();
+                target.insert(String::from("$ref"), reference);
+                return json!({ "allOf": [target, res] })
+            }
+
+            Value::Object(res)
+        },
+        Value::Array(values) => Value::Array(values.iter().map(expand_refs).collect()),
+        _ => value.clone()
+    }
+}
+
+fn required(map: &Map<String, Value>) -> Vec<&str> {
+    map.get("required").and_then(|x| x.as_array()).map(|xs| xs.iter().filter_map(|x| x.as_str()).collect()).unwrap_or_default()
+}
+
+pub struct JsonSchemaCompatibility;
+
+impl JsonSchemaCompatibility {
+    pub fn incompatibilities(writers_schema: &JsonSchema, readers_schema: &JsonSchema) -> Vec<Incompatibility> {
+        let expand = |references: &HashMap<String, Value>| references.iter().map(|(name, value)| (name.clone(), expand_refs(value))).collect::<HashMap<String, Value>>();
+        let (writers_references, readers_references) = (expand(&writers_schema.references), expand(&readers_schema.references));
+        let (writer, reader) = (expand_refs(&writers_schema.value), expand_refs(&readers_schema.value));
+
+        let mut checker = Checker {
+            writers_references: &writers_references,
+            readers_references: &readers_references,
+            in_progress: HashSet::new(),
+            res: vec![]
+        };
+
+        checker.check("", Node::root(&writer), Node::root(&reader));
+        checker.res
+    }
+}
+
+// a subschema together with the document its local `$ref`s point into
+#[derive(Clone, Copy)]
+struct Node<'a> {
+    root: &'a Value,
+    value: &'a Value
+}
+
+impl <'a> Node<'a> {
+    fn root(value: &'a Value) -> Node<'a> {
+        Node { root: value, value }
+    }
+
+    fn child(&self, value: &'a Value) -> Node<'a> {
+        Node { root: self.root, value }
+    }
+}
+
+// collects why documents accepted by the writer's schema would be rejected by the reader's schema
+struct Checker<'a> {
+    writers_references: &'a HashMap<String, Value>,
+    readers_references: &'a HashMap<String, Value>,
+    in_progress: HashSet<(*const Value, *const Value)>,
+    res: Vec<Incompatibility>
+}
+
+impl <'a> Checker<'a> {
+    fn resolve(references: &'a HashMap<String, Value>, node: Node<'a>) -> Node<'a> {
+        let mut current = node;
+
+        for _ in 0..MAX_REF_DEPTH {
+            let reference = match current.value.get("$ref").and_then(|x| x.as_str()) {
+                Some(reference) => reference,
+                None => return current
+            };
+
+            let (document, pointer) = reference.split_once('#').unwrap_or((reference, ""));
+            let root = match document {
+                "" => Some(current.root),
+                _ => references.get(document)
+            };
+
+            match root.and_then(|x| x.pointer(pointer).map(|value| Node { root: x, value })) {
+                Some(target) => current = target,
+                None => return current
+            }
+        }
+
+        current
+    }
+
+    fn report(&mut self, path: &str, reason: &str, writer: &Value, reader: &Value) {
+        let path = if path.is_empty() { "/" } else { path };
+        self.res.push(Incompatibility::new(path, reason, &describe(reader), &describe(writer)));
+    }
+
+    // whether the reader accepts everything the writer accepts, without reporting anything
+    fn probe(&mut self, path: &str, writer: Node<'a>, reader: Node<'a>) -> bool {
+        let len = self.res.len();
+        self.check(path, writer, reader);
+        let res = self.res.len() == len;
+        self.res.truncate(len);
+        res
+    }
+
+    fn check(&mut self, path: &str, writer: Node<'a>, reader: Node<'a>) {
+        let writer = Self::resolve(self.writers_references, writer);
+        let reader = Self::resolve(self.readers_references, reader);
+
+        // recursive definitions are assumed compatible while the same pair is still being compared
+        let key = (writer.value as *const Value, reader.value as *const Value);
+        if !self.in_progress.insert(key) {
+            return
+        }
+
+        match (writer.value, reader.value) {
+            (_, Value::Bool(true)) => (),
+            (Value::Bool(false), _) => (),
+            (_, Value::Bool(false)) => self.report(path, "the reader rejects every value", writer.value, reader.value),
+            (Value::Bool(true), _) => {
+                if !is_empty_schema(reader.value) {
+                    self.report(path, "the writer accepts any value but the reader is constrained", writer.value, reader.value);
+                }
+            },
+            (Value::Object(writers_map), Value::Object(readers_map)) => self.check_objects(path, writer, writers_map, reader, readers_map),
+            _ => self.report(path, "the schemas cannot be compared", writer.value, reader.value)
+        }
+
+        self.in_progress.remove(&key);
+    }
+
+    fn check_objects(&mut self, path: &str, writers_node: Node<'a>, writer: &'a Map<String, Value>, readers_node: Node<'a>, reader: &'a Map<String, Value>) {
+        if let Some((keyword, branches)) = union(writer) {
+            for (i, branch) in branches.iter().enumerate() {
+                self.check(&format!("{}/{}/{}", path, keyword, i), writers_node.child(branch), readers_node);
+            }
+            return
+        }
+
+        if let Some(branches) = reader.get("allOf").and_then(|x| x.as_array()) {
+            for (i, branch) in branches.iter().enumerate() {
+                self.check(&format!("{}/allOf/{}", path, i), writers_node, readers_node.child(branch));
+            }
+            return
+        }
+
+        if let Some((keyword, branches)) = union(reader) {
+            if !branches.iter().enumerate().any(|(i, x)| self.probe(&format!("{}/{}/{}", path, keyword, i), writers_node, readers_node.child(x))) {
+                self.report(path, &format!("no branch of the reader's {} accepts the writer", keyword), writers_node.value, readers_node.value);
+            }
+            return
+        }
+
+        if let Some(branches) = writer.get("allOf").and_then(|x| x.as_array()) {
+            if !branches.iter().any(|x| self.probe(path, writers_node.child(x), readers_node)) {
+                self.report(path, "the reader accepts no branch of the writer's allOf", writers_node.value, readers_node.value);
+            }
+            return
+        }
+
+        let reasons: Vec<String> = [Self::types_incompatibility(writer, reader), Self::values_incompatibility(writer, reader)]
+            .into_iter()
+            .flatten()
+            .chain(Self::bounds_incompatibilities(writer, reader))
+            .collect();
+
+        for reason in reasons {
+            self.report(path, &reason, writers_node.value, readers_node.value);
+        }
+
+        self.check_properties(path, writers_node, writer, readers_node, reader);
+        self.check_items(path, writers_node, writer, readers_node, reader);
+    }
+
+    fn types_incompatibility(writer: &Map<String, Value>, reader: &Map<String, Value>) -> Option<String> {
+        let compatible = match (types(writer), types(reader)) {
+            (_, None) => true,
+            (None, Some(_)) => false,
+            (Some(writers_types), Some(readers_types)) => writers_types.iter().all(|x| {
+                readers_types.contains(x) || (*x == "integer" && readers_types.contains(&"number"))
+            })
+        };
+
+        if compatible { None } else { Some(String::from("the reader's type does not accept every type of the writer")) }
+    }
+
+    fn values_incompatibility(writer: &Map<String, Value>, reader: &Map<String, Value>) -> Option<String> {
+        let writers_values = match (writer.get("const"), writer.get("enum")) {
+            (Some(value), _) => Some(vec![value]),
+            (None, Some(Value::Array(values))) => Some(values.iter().collect()),
+            _ => None
+        };
+
+        if let Some(readers_values) = reader.get("enum").and_then(|x| x.as_array()) {
+            match &writers_values {
+                Some(values) if values.iter().all(|x| readers_values.contains(x)) => (),
+                _ => return Some(String::from("the reader's enum does not contain every value of the writer"))
+            }
+        }
+
+        if let Some(readers_const) = reader.get("const") {
+            match &writers_values {
+                Some(values) if values.iter().all(|x| *x == readers_const) => (),
+                _ => return Some(String::from("the reader's const does not match the writer's values"))
+            }
+        }
+
+        for keyword in ["pattern", "format"] {
+            if let Some(readers_value) = reader.get(keyword) {
+                if writer.get(keyword) != Some(readers_value) {
+                    return Some(format!("the reader's {} differs from the writer's", keyword))
+                }
+            }
+        }
+
+        None
+    }
+
+    fn bounds_incompatibilities(writer: &Map<String, Value>, reader: &Map<String, Value>) -> Vec<String> {
+        let number = |map: &Map<String, Value>, keyword: &str| map.get(keyword).and_then(|x| x.as_f64());
+        let mut res = vec![];
+
+        for keyword in LOWER_BOUNDS {
+            if let Some(readers_bound) = number(reader, keyword) {
+                match number(writer, keyword) {
+                    Some(writers_bound) if writers_bound >= readers_bound => (),
+                    _ => res.push(format!("the reader's {} is stricter than the writer's", keyword))
+                }
+            }
+        }
+
+        for keyword in UPPER_BOUNDS {
+            if let Some(readers_bound) = number(reader, keyword) {
+                match number(writer, keyword) {
+                    Some(writers_bound) if writers_bound <= readers_bound => (),
+                    _ => res.push(format!("the reader's {} is stricter than the writer's", keyword))
+                }
+            }
+        }
+
+        if let Some(readers_multiple) = number(reader, "multipleOf") {
+            match number(writer, "multipleOf") {
+                Some(writers_multiple) if (writers_multiple / readers_multiple).fract() == 0.0 => (),
+                _ => res.push(String::from("the reader's multipleOf is stricter than the writer's"))
+            }
+        }
+
+        if reader.get("uniqueItems") == Some(&Value::Bool(true)) && writer.get("uniqueItems") != Some(&Value::Bool(true)) {
+            res.push(String::from("the reader requires unique items"));
+        }
+
+        res
+    }
+
+    fn check_properties(&mut self, path: &str, writers_node: Node<'a>, writer: &'a Map<String, Value>, readers_node: Node<'a>, reader: &'a Map<String, Value>) {
+        let writers_properties = writer.get("properties").and_then(|x| x.as_object());
+        let readers_properties = reader.get("properties").and_then(|x| x.as_object());
+
+        // an absent additionalProperties keeps the content model open
+        let writers_additional = writers_node.child(writer.get("additionalProperties").unwrap_or(&TRUE));
+        let readers_additional = readers_node.child(reader.get("additionalProperties").unwrap_or(&TRUE));
+
+        for (name, writers_property) in writers_properties.into_iter().flatten() {
+            let readers_property = readers_properties.and_then(|x| x.get(name)).map(|x| readers_node.child(x)).unwrap_or(readers_additional);
+            self.check(&format!("{}/properties/{}", path, name), writers_node.child(writers_property), readers_property);
+        }
+
+        for (name, readers_property) in readers_properties.into_iter().flatten() {
+            let is_added = !writers_properties.map(|x| x.contains_key(name)).unwrap_or(false);
+            if is_added {
+                self.check(&format!("{}/properties/{}", path, name), writers_additional, readers_node.child(readers_property));
+            }
+        }
+
+        self.check(&format!("{}/additionalProperties", path), writers_additional, readers_additional);
+
+        let writers_required = required(writer);
+
+        for name in required(reader) {
+            let readers_property = readers_properties.and_then(|x| x.get(name));
+            let has_default = readers_property.map(|x| x.get("default").is_some()).unwrap_or(false);
+
+            if !writers_required.contains(&name) && !has_default {
+                let reader_type = describe(readers_property.unwrap_or(&TRUE));
+                self.res.push(Incompatibility::new(&format!("{}/properties/{}", path, name), "the reader requires a property the writer does not require", &reader_type, "optional"));
+            }
+        }
+    }
+
+    fn check_items(&mut self, path: &str, writers_node: Node<'a>, writer: &'a Map<String, Value>, readers_node: Node<'a>, reader: &'a Map<String, Value>) {
+        let writers_prefix = writer.get("prefixItems").or_else(|| writer.get("items").filter(|x| x.is_array())).and_then(|x| x.as_array());
+        let readers_prefix = reader.get("prefixItems").or_else(|| reader.get("items").filter(|x| x.is_array())).and_then(|x| x.as_array());
+
+        let writers_items = writer.get("items").filter(|x| !x.is_array()).or_else(|| writer.get("additionalItems")).unwrap_or(&TRUE);
+        let readers_items = reader.get("items").filter(|x| !x.is_array()).or_else(|| reader.get("additionalItems")).unwrap_or(&TRUE);
+
+        let writers_prefix = writers_prefix.map(|x| x.as_slice()).unwrap_or_default();
+        let readers_prefix = readers_prefix.map(|x| x.as_slice()).unwrap_or_default();
+
+        for i in 0..writers_prefix.len().max(readers_prefix.len()) {
+            let writers_item = writers_prefix.get(i).unwrap_or(writers_items);
+            let readers_item = readers_prefix.get(i).unwrap_or(readers_items);
+
+            self.check(&format!("{}/prefixItems/{}", path, i), writers_node.child(writers_item), readers_node.child(readers_item));
+        }
+
+        self.check(&format!("{}/items", path), writers_node.child(writers_items), readers_node.child(readers_items));
+    }
+}
+
+static TRUE: Value = Value::Bool(true);
+
+pub struct JsonSchemaProvider;
+
+impl SchemaProvider for JsonSchemaProvider {
+    type Schema = JsonSchema;
+
+    fn schema_type(&self) -> &'static str {
+        "JSON"
+    }
+
+    fn parse(&self, schema: &str, references: &[ResolvedReference]) -> Result<JsonSchema, InvalidSchema> {
+        JsonSchema::parse_str(schema, references)
+    }
+
+    fn canonical_form(&self, schema: &JsonSchema) -> String {
+        schema.canonical_form()
+    }
+
+    fn incompatibilities(&self, writers_schema: &JsonSchema, readers_schema: &JsonSchema) -> Vec<Incompatibility> {
+        JsonSchemaCompatibility::incompatibilities(writers_schema, readers_schema)
+    }
+
+    fn references(&self, schema: &JsonSchema) -> Vec<String> {
+        let mut res: Vec<String> = schema.external_refs().iter().map(|x| x.split_once('#').map(|x| x.0).unwrap_or(x).to_string()).collect();
+        res.sort();
+        res.dedup();
+        res
+    }
+}
@@ -0,0 +1,322 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sqlx::Error;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::data::*;
+use crate::memory_repository::{Change, InMemoryRepository};
+use crate::repository::Repository;
+
+// an append-only file of changes, one json record per line
+struct Segment {
+    path: PathBuf,
+    file: File,
+    // records appended since the last compaction
+    appended: usize
+}
+
+impl Segment {
+    async fn open(path: PathBuf) -> std::io::Result<Segment> {
+        let file = OpenOptions::new().create(true).append(true).open(&path).await?;
+        Ok(Segment { path, file, appended: 0 })
+    }
+
+    fn encode(changes: &[Change]) -> std::io::Result<Vec<u8>> {
+        let mut buf = vec![];
+        for change in changes {
+            serde_json::to_writer(&mut buf, change)?;
+            buf.push(b'\n');
+        }
+        Ok(buf)
+    }
+
+    async fn append(&mut self, changes: &[Change]) -> std::io::Result<()> {
+        self.file.write_all(&Self::encode(changes)?).await?;
+        self.file.sync_data().await?;
+        self.appended += changes.len();
+        Ok(())
+    }
+
+    // writes the snapshot next to the segment and swaps it in, so a crash leaves either the old or the new file
+    async fn replace(&mut self, snapshot: &[Change]) -> std::io::Result<()> {
+        let compacting = self.path.with_extension("compacting");
+
+        let mut file = File::create(&compacting).await?;
+        file.write_all(&Self::encode(snapshot)?).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&compacting, &self.path).await?;
+
+        *self = Segment::open(self.path.clone()).await?;
+        Ok(())
+    }
+}
+
+// the registry as the log of its changes, rebuilt into an InMemoryRepository on startup
+// changes are written to the log before they are applied, the log mutex orders the writes
+#[derive(Clone)]
+pub struct LogRepository {
+    memory: InMemoryRepository,
+    segment: Arc<Mutex<Segment>>
+}
+
+impl LogRepository {
+    pub async fn open(path: impl AsRef<Path>) -> std::io::Result<LogRepository> {
+        let path = path.as_ref().to_path_buf();
+        let memory = InMemoryRepository::default();
+
+        let content = match tokio::fs::read(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e)
+        };
+
+        // a record without its newline was torn by a crash while appending and is dropped
+        let complete = content.iter().rposition(|x| *x == b'\n').map(|x| x + 1).unwrap_or(0);
+        if complete < content.len() {
+            std::fs::OpenOptions::new().write(true).open(&path)?.set_len(complete as u64)?;
+        }
+
+        {
+            let mut tables = memory.tables();
+            for (index, line) in content[..complete].split(|x| *x == b'\n').filter(|x| !x.is_empty()).enumerate() {
+                let change: Change = serde_json::from_slice(line)
+                    .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, format!("{} record {}: {}", path.display(), index + 1, e)))?;
+                tables.apply(change);
+            }
+        }
+
+        let segment = Segment::open(path).await?;
+        Ok(LogRepository { memory, segment: Arc::new(Mutex::new(segment)) })
+    }
+
+    async fn commit(&self, segment: &mut Segment, changes: Vec<Change>) -> Result<(), Error> {
+        segment.append(&changes).await?;
+
+        let mut tables = self.memory.tables();
+        for change in changes {
+            tables.apply(change);
+        }
+
+        Ok(())
+    }
+
+    // rewrites the log as the changes that rebuild the current state, dropping the ones that were overwritten since
+    pub async fn compact(&self) -> std::io::Result<()> {
+        let mut segment = self.segment.lock().await;
+        if segment.appended == 0 {
+            return Ok(())
+        }
+
+        let snapshot = self.memory.tables().snapshot();
+        segment.replace(&snapshot).await
+    }
+
+    pub fn compact_every(&self, period: Duration) {
+        let repository = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = repository.compact().await {
+                    eprintln!("log compaction failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl Repository for LogRepository {
+
+    async fn schema_find_by_id(&self, id: i64) -> Result<Option<SchemaPayload>, Error> {
+        self.memory.schema_find_by_id(id).await
+    }
+
+    async fn schema_soft_delete(&self, subject: &str, schema_id: i64) -> Result<u64, Error> {
+        let mut segment = self.segment.lock().await;
+        let (affected, changes) = self.memory.tables().schema_soft_delete(subject, schema_id);
+        self.commit(&mut segment, changes).await?;
+
+        Ok(affected)
+    }
+
+    async fn subject_soft_delete(&self, subject_name: &str) -> Result<Vec<i64>, Error> {
+        let mut segment = self.segment.lock().await;
+        let (schema_ids, changes) = self.memory.tables().subject_soft_delete(subject_name);
+        self.commit(&mut segment, changes).await?;
+
+        Ok(schema_ids)
+    }
+
+    async fn subject_deleted(&self, subject: &str) -> Result<Option<bool>, Error> {
+        self.memory.subject_deleted(subject).await
+    }
+
+    async fn schema_version_deleted(&self, subject: &str, version: i32) -> Result<Option<bool>, Error> {
+        self.memory.schema_version_deleted(subject, version).await
+    }
+
+    async fn schema_permanent_delete(&self, subject: &str, version: i32) -> Result<u64, Error> {
+        let mut segment = self.segment.lock().await;
+        let (affected, changes) = self.memory.tables().schema_permanent_delete(subject, version);
+        self.commit(&mut segment, changes).await?;
+
+        Ok(affected)
+    }
+
+    async fn subject_permanent_delete(&self, subject: &str) -> Result<Vec<i64>, Error> {
+        let mut segment = self.segment.lock().await;
+        let (schema_ids, changes) = self.memory.tables().subject_permanent_delete(subject);
+        self.commit(&mut segment, changes).await?;
+
+        Ok(schema_ids)
+    }
+
+    async fn schema_undelete(&self, subject: &str, version: i32) -> Result<u64, Error> {
+        let mut segment = self.segment.lock().await;
+        let (affected, changes) = self.memory.tables().schema_undelete(subject, version);
+        self.commit(&mut segment, changes).await?;
+
+        Ok(affected)
+    }
+
+    async fn subject_undelete(&self, subject: &str) -> Result<Vec<i64>, Error> {
+        let mut segment = self.segment.lock().await;
+        let (schema_ids, changes) = self.memory.tables().subject_undelete(subject);
+        self.commit(&mut segment, changes).await?;
+
+        Ok(schema_ids)
+    }
+
+    async fn schema_find_by_version(&self, subject: &str, version: i32, deleted: bool) -> Result<Option<SubjectSchema>, Error> {
+        self.memory.schema_find_by_version(subject, version, deleted).await
+    }
+
+    async fn schema_find_by_schema(&self, subject: &str, fingerprint: &str, normalize: bool) -> Result<Option<SubjectSchema>, Error> {
+        self.memory.schema_find_by_schema(subject, fingerprint, normalize).await
+    }
+
+    async fn insert(&self, subject: &str, fingerprints: &Fingerprints, schema: &str, schema_type: &SchemaType, references: &[SchemaReference], max_version: i32) -> Result<Option<i64>, Error> {
+        let mut segment = self.segment.lock().await;
+        let res = self.memory.tables().insert(subject, fingerprints, schema, schema_type, references, max_version);
+
+        match res {
+            Some((schema_id, changes)) => {
+                self.commit(&mut segment, changes).await?;
+                Ok(Some(schema_id))
+            },
+            None => Ok(None)
+        }
+    }
+
+    async fn schema_import(&self, subject: &str, version: i32, id: i64, fingerprints: &Fingerprints, payload: &SchemaPayload) -> Result<SchemaImport, Error> {
+        let mut segment = self.segment.lock().await;
+        let (res, changes) = self.memory.tables().schema_import(subject, version, id, fingerprints, payload);
+        self.commit(&mut segment, changes).await?;
+
+        Ok(res)
+    }
+
+    async fn schema_fingerprints(&self) -> Result<Vec<StoredFingerprints>, Error> {
+        self.memory.schema_fingerprints().await
+    }
+
+    async fn schema_fingerprints_update(&self, id: i64, fingerprints: &Fingerprints) -> Result<(), Error> {
+        let mut segment = self.segment.lock().await;
+        let changes = self.memory.tables().schema_fingerprints_update(id, fingerprints);
+        self.commit(&mut segment, changes).await?;
+
+        Ok(())
+    }
+
+    async fn schema_references(&self, schema_id: i64) -> Result<Vec<SchemaReference>, Error> {
+        self.memory.schema_references(schema_id).await
+    }
+
+    async fn schema_subjects(&self, schema_id: i64, deleted: bool) -> Result<Vec<String>, Error> {
+        self.memory.schema_subjects(schema_id, deleted).await
+    }
+
+    async fn schema_versions(&self, schema_id: i64, deleted: bool) -> Result<Vec<SubjectVersion>, Error> {
+        self.memory.schema_versions(schema_id, deleted).await
+    }
+
+    async fn schema_all(&self, subject_prefix: &str, latest_only: bool, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<SubjectSchema>, Error> {
+        self.memory.schema_all(subject_prefix, latest_only, deleted, offset, limit).await
+    }
+
+    async fn schema_referenced_by(&self, subject: &str, version: i32) -> Result<Vec<i64>, Error> {
+        self.memory.schema_referenced_by(subject, version).await
+    }
+
+    async fn subject_versions(&self, subject: &str, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<i32>, Error> {
+        self.memory.subject_versions(subject, deleted, offset, limit).await
+    }
+
+    async fn subject_find(&self, subject: &str) -> Result<Option<Subject>, Error> {
+        self.memory.subject_find(subject).await
+    }
+
+    async fn subject_find_deleted(&self, subject: &str) -> Result<Option<Subject>, Error> {
+        self.memory.subject_find_deleted(subject).await
+    }
+
+    async fn subject_reserve(&self, subject: &str) -> Result<Subject, Error> {
+        let mut segment = self.segment.lock().await;
+        let (res, changes) = self.memory.tables().subject_reserve(subject);
+        self.commit(&mut segment, changes).await?;
+
+        Ok(res)
+    }
+
+    async fn subject_all(&self, subject_prefix: &str, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<Subject>, Error> {
+        self.memory.subject_all(subject_prefix, deleted, offset, limit).await
+    }
+
+    async fn subject_schemas(&self, subject: &str) -> Result<Vec<VersionedSchema>, Error> {
+        self.memory.subject_schemas(subject).await
+    }
+
+    async fn config_get_subject(&self, subject_id: Option<i64>) -> Result<Option<Config>, Error> {
+        self.memory.config_get_subject(subject_id).await
+    }
+
+    async fn config_set_subject(&self, subject_id: Option<i64>, config: &Config) -> Result<(), Error> {
+        let mut segment = self.segment.lock().await;
+        let changes = self.memory.tables().config_set_subject(subject_id, config);
+        self.commit(&mut segment, changes).await?;
+
+        Ok(())
+    }
+
+    async fn config_delete_subject(&self, subject_id: Option<i64>) -> Result<Option<Config>, Error> {
+        let mut segment = self.segment.lock().await;
+        let (res, changes) = self.memory.tables().config_delete_subject(subject_id);
+        self.commit(&mut segment, changes).await?;
+
+        Ok(res)
+    }
+
+    async fn max_version(&self, subject: &str, deleted: bool) -> Result<Option<MaxVersion>, Error> {
+        self.memory.max_version(subject, deleted).await
+    }
+
+    async fn mode_get_subject(&self, subject_id: Option<i64>) -> Result<Option<Mode>, Error> {
+        self.memory.mode_get_subject(subject_id).await
+    }
+
+    async fn mode_set_subject(&self, subject_id: Option<i64>, mode: &Mode) -> Result<(), Error> {
+        let mut segment = self.segment.lock().await;
+        let changes = self.memory.tables().mode_set_subject(subject_id, mode);
+        self.commit(&mut segment, changes).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,780 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::Error;
+
+use crate::data::*;
+use crate::repository::Repository;
+
+struct SubjectRow {
+    id: i64,
+    name: String,
+    deleted: bool
+}
+
+struct SchemaRow {
+    id: i64,
+    fingerprint: String,
+    lookup_fingerprint: Option<String>,
+    json: String,
+    schema_type: SchemaType,
+    deleted: bool
+}
+
+struct SchemaVersionRow {
+    version: i32,
+    subject_id: i64,
+    schema_id: i64,
+    deleted: bool
+}
+
+struct ReferenceRow {
+    schema_id: i64,
+    reference: SchemaReference
+}
+
+struct ConfigRow {
+    subject_id: Option<i64>,
+    config: Config
+}
+
+struct ModeRow {
+    subject_id: Option<i64>,
+    mode: Mode
+}
+
+// a write to the tables, the latest change of a row is the state of that row
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Change {
+    Subject { name: String, deleted: bool },
+    // logs written before lookup fingerprints existed leave them out
+    Schema { id: i64, fingerprint: String, lookup_fingerprint: Option<String>, schema: String, schema_type: SchemaType, references: Vec<SchemaReference>, deleted: bool },
+    Version { subject: String, version: i32, id: i64, deleted: bool },
+    // the config fields sit next to the subject, as `compatibility` in logs written before the other fields existed
+    Config { subject: Option<String>, #[serde(flatten)] config: Box<Config> },
+    Mode { subject: Option<String>, mode: Mode },
+    RemoveConfig { subject: Option<String> },
+    // permanent deletes, a removed subject takes its versions, configs and modes along
+    RemoveVersion { subject: String, version: i32 },
+    RemoveSchema { id: i64 },
+    RemoveSubject { name: String },
+    // keeps the ids of removed schemas from being handed out again after a compaction
+    SchemaSequence { last_id: i64 }
+}
+
+// the writes only compute their changes, which `apply` then makes to the tables
+#[derive(Default)]
+pub struct Tables {
+    subjects: Vec<SubjectRow>,
+    schemas: Vec<SchemaRow>,
+    schema_versions: Vec<SchemaVersionRow>,
+    references: Vec<ReferenceRow>,
+    configs: Vec<ConfigRow>,
+    modes: Vec<ModeRow>,
+    // the last ids handed out, like the sequences of the postgres tables
+    last_subject_id: i64,
+    last_schema_id: i64
+}
+
+impl Tables {
+    fn subject(&self, name: &str) -> Option<&SubjectRow> {
+        self.subjects.iter().find(|x| x.name == name)
+    }
+
+    fn live_subject(&self, name: &str) -> Option<&SubjectRow> {
+        self.subject(name).filter(|x| !x.deleted)
+    }
+
+    fn live_schema(&self, id: i64) -> Option<&SchemaRow> {
+        self.schemas.iter().find(|x| x.id == id && !x.deleted)
+    }
+
+    // the joins of subjects, schema_versions and schemas, which skip soft deleted rows unless `deleted` is set
+    fn versions(&self, subject: &str, deleted: bool) -> Vec<(&SchemaVersionRow, &SchemaRow)> {
+        let subject_id = match self.subject(subject).filter(|x| deleted || !x.deleted) {
+            Some(subject) => subject.id,
+            None => return vec![]
+        };
+
+        self.schema_versions
+            .iter()
+            .filter(|x| x.subject_id == subject_id && (deleted || !x.deleted))
+            .filter_map(|x| self.schemas.iter().find(|schema| schema.id == x.schema_id && (deleted || !schema.deleted)).map(|schema| (x, schema)))
+            .collect()
+    }
+
+    fn live_versions(&self, subject: &str) -> Vec<(&SchemaVersionRow, &SchemaRow)> {
+        self.versions(subject, false)
+    }
+
+    fn subject_schema(subject: &str, version: &SchemaVersionRow, schema: &SchemaRow) -> SubjectSchema {
+        SubjectSchema { subject: subject.to_string(), version: version.version, id: schema.id, schema: schema.json.clone(), schema_type: schema.schema_type.clone() }
+    }
+
+    // `deleted` counts soft deleted versions too, see Repository::max_version
+    fn max_version(&self, subject: &str, deleted: bool) -> Option<i32> {
+        let subject_id = self.subject(subject)?.id;
+        self.schema_versions.iter().filter(|x| x.subject_id == subject_id && (deleted || !x.deleted)).map(|x| x.version).max()
+    }
+
+    fn schema_references(&self, schema_id: i64) -> Vec<SchemaReference> {
+        self.references.iter().filter(|x| x.schema_id == schema_id).map(|x| x.reference.clone()).collect()
+    }
+
+    fn schema_change(&self, schema: &SchemaRow, deleted: bool) -> Change {
+        Change::Schema {
+            id: schema.id,
+            fingerprint: schema.fingerprint.clone(),
+            lookup_fingerprint: schema.lookup_fingerprint.clone(),
+            schema: schema.json.clone(),
+            schema_type: schema.schema_type.clone(),
+            references: self.schema_references(schema.id),
+            deleted
+        }
+    }
+
+    // the changes of Repository::insert, None when the subject moved past `max_version`
+    pub fn insert(&self, subject: &str, fingerprints: &Fingerprints, schema: &str, schema_type: &SchemaType, references: &[SchemaReference], max_version: i32) -> Option<(i64, Vec<Change>)> {
+        if self.max_version(subject, true).unwrap_or(0) != max_version {
+            return None
+        }
+
+        let id = match self.schemas.iter().find(|x| x.fingerprint == fingerprints.fingerprint) {
+            Some(row) => row.id,
+            None => self.last_schema_id + 1
+        };
+
+        let changes = vec![
+            Change::Subject { name: subject.to_string(), deleted: false },
+            Change::Schema { id, fingerprint: fingerprints.fingerprint.clone(), lookup_fingerprint: Some(fingerprints.lookup_fingerprint.clone()), schema: schema.to_string(), schema_type: schema_type.clone(), references: references.to_vec(), deleted: false },
+            Change::Version { subject: subject.to_string(), version: max_version + 1, id, deleted: false }
+        ];
+
+        Some((id, changes))
+    }
+
+    // the changes of Repository::schema_import, with what it found
+    pub fn schema_import(&self, subject: &str, version: i32, id: i64, fingerprints: &Fingerprints, payload: &SchemaPayload) -> (SchemaImport, Vec<Change>) {
+        if self.schemas.iter().any(|x| x.id == id && x.fingerprint != fingerprints.fingerprint) {
+            return (SchemaImport::IdTaken, vec![])
+        }
+        // a version keeps its schema, importing it again changes nothing
+        if let Some((row, _)) = self.versions(subject, true).into_iter().find(|(x, _)| x.version == version) {
+            return match row.schema_id == id {
+                true => (SchemaImport::Imported, vec![]),
+                false => (SchemaImport::VersionTaken, vec![])
+            }
+        }
+        if let Some(row) = self.schemas.iter().find(|x| x.fingerprint == fingerprints.fingerprint && x.id != id) {
+            return (SchemaImport::OtherId(row.id), vec![])
+        }
+
+        let changes = vec![
+            Change::Subject { name: subject.to_string(), deleted: false },
+            Change::Schema { id, fingerprint: fingerprints.fingerprint.clone(), lookup_fingerprint: Some(fingerprints.lookup_fingerprint.clone()), schema: payload.schema.clone(), schema_type: payload.schema_type.clone(), references: payload.references.clone(), deleted: false },
+            Change::Version { subject: subject.to_string(), version, id, deleted: false }
+        ];
+
+        (SchemaImport::Imported, changes)
+    }
+
+    // the changes of Repository::subject_reserve
+    pub fn subject_reserve(&self, subject: &str) -> (Subject, Vec<Change>) {
+        match self.subject(subject) {
+            Some(row) => (Subject { id: row.id, name: row.name.clone() }, vec![]),
+            None => (Subject { id: self.last_subject_id + 1, name: subject.to_string() }, vec![Change::Subject { name: subject.to_string(), deleted: true }])
+        }
+    }
+
+    // the changes of Repository::schema_fingerprints_update
+    pub fn schema_fingerprints_update(&self, id: i64, fingerprints: &Fingerprints) -> Vec<Change> {
+        let schema = match self.schemas.iter().find(|x| x.id == id) {
+            Some(schema) => schema,
+            None => return vec![]
+        };
+
+        let fingerprint = match self.schemas.iter().any(|x| x.fingerprint == fingerprints.fingerprint && x.id != id) {
+            true => schema.fingerprint.clone(),
+            false => fingerprints.fingerprint.clone()
+        };
+
+        vec![Change::Schema {
+            id,
+            fingerprint,
+            lookup_fingerprint: Some(fingerprints.lookup_fingerprint.clone()),
+            schema: schema.json.clone(),
+            schema_type: schema.schema_type.clone(),
+            references: self.schema_references(id),
+            deleted: schema.deleted
+        }]
+    }
+
+    // the changes of Repository::schema_soft_delete, with the number of deleted versions
+    pub fn schema_soft_delete(&self, subject: &str, schema_id: i64) -> (u64, Vec<Change>) {
+        let subject_id = self.subject(subject).map(|x| x.id);
+
+        let mut changes: Vec<Change> = self.schema_versions
+            .iter()
+            .filter(|x| x.schema_id == schema_id && Some(x.subject_id) == subject_id && !x.deleted)
+            .map(|x| Change::Version { subject: subject.to_string(), version: x.version, id: schema_id, deleted: true })
+            .collect();
+        let affected = changes.len() as u64;
+
+        let orphaned = !self.schema_versions.iter().any(|x| x.schema_id == schema_id && Some(x.subject_id) != subject_id && !x.deleted);
+        if let Some(schema) = self.schemas.iter().find(|x| x.id == schema_id && orphaned) {
+            changes.push(self.schema_change(schema, true));
+        }
+
+        (affected, changes)
+    }
+
+    // the changes of Repository::subject_soft_delete, with the ids of the schemas the subject held
+    pub fn subject_soft_delete(&self, subject: &str) -> (Vec<i64>, Vec<Change>) {
+        let subject_id = match self.subject(subject) {
+            Some(row) => row.id,
+            None => return (vec![], vec![])
+        };
+
+        let versions: Vec<&SchemaVersionRow> = self.schema_versions.iter().filter(|x| x.subject_id == subject_id && !x.deleted).collect();
+
+        let mut changes = vec![Change::Subject { name: subject.to_string(), deleted: true }];
+        changes.extend(versions.iter().map(|x| Change::Version { subject: subject.to_string(), version: x.version, id: x.schema_id, deleted: true }));
+
+        (versions.iter().map(|x| x.schema_id).collect(), changes)
+    }
+
+    // the changes of Repository::schema_undelete, with the number of restored versions
+    pub fn schema_undelete(&self, subject: &str, version: i32) -> (u64, Vec<Change>) {
+        let restored = self.versions(subject, true).into_iter().find(|(x, _)| x.version == version && x.deleted);
+
+        match restored {
+            Some((x, schema)) => {
+                let changes = vec![
+                    Change::Subject { name: subject.to_string(), deleted: false },
+                    self.schema_change(schema, false),
+                    Change::Version { subject: subject.to_string(), version, id: x.schema_id, deleted: false }
+                ];
+                (1, changes)
+            },
+            None => (0, vec![])
+        }
+    }
+
+    // the changes of Repository::subject_undelete, with the ids of the schemas of the restored versions
+    pub fn subject_undelete(&self, subject: &str) -> (Vec<i64>, Vec<Change>) {
+        if self.subject(subject).is_none() {
+            return (vec![], vec![])
+        }
+
+        let restored: Vec<(&SchemaVersionRow, &SchemaRow)> = self.versions(subject, true).into_iter().filter(|(x, _)| x.deleted).collect();
+
+        let mut changes = vec![Change::Subject { name: subject.to_string(), deleted: false }];
+        for (x, schema) in &restored {
+            changes.push(self.schema_change(schema, false));
+            changes.push(Change::Version { subject: subject.to_string(), version: x.version, id: x.schema_id, deleted: false });
+        }
+
+        (restored.iter().map(|(x, _)| x.schema_id).collect(), changes)
+    }
+
+    // schemas still used by a version of any subject, soft deleted or not, are kept
+    fn remove_unused_schemas(&self, removed: &[&SchemaVersionRow]) -> Vec<Change> {
+        let is_removed = |row: &SchemaVersionRow| removed.iter().any(|x| x.subject_id == row.subject_id && x.version == row.version);
+        let is_used = |id: i64| self.schema_versions.iter().any(|x| x.schema_id == id && !is_removed(x));
+
+        let mut schema_ids: Vec<i64> = removed.iter().map(|x| x.schema_id).filter(|x| !is_used(*x)).collect();
+        schema_ids.sort();
+        schema_ids.dedup();
+
+        schema_ids.into_iter().map(|id| Change::RemoveSchema { id }).collect()
+    }
+
+    // the changes of Repository::schema_permanent_delete, with the number of removed versions
+    pub fn schema_permanent_delete(&self, subject: &str, version: i32) -> (u64, Vec<Change>) {
+        let subject_id = self.subject(subject).map(|x| x.id);
+        let removed: Vec<&SchemaVersionRow> = self.schema_versions.iter().filter(|x| Some(x.subject_id) == subject_id && x.version == version && x.deleted).collect();
+
+        let mut changes: Vec<Change> = removed.iter().map(|x| Change::RemoveVersion { subject: subject.to_string(), version: x.version }).collect();
+        changes.extend(self.remove_unused_schemas(&removed));
+
+        (removed.len() as u64, changes)
+    }
+
+    // the changes of Repository::subject_permanent_delete, with the ids of the schemas the subject held
+    pub fn subject_permanent_delete(&self, subject: &str) -> (Vec<i64>, Vec<Change>) {
+        let subject_id = match self.subject(subject).filter(|x| x.deleted) {
+            Some(row) => row.id,
+            None => return (vec![], vec![])
+        };
+        let removed: Vec<&SchemaVersionRow> = self.schema_versions.iter().filter(|x| x.subject_id == subject_id).collect();
+
+        let mut changes = vec![Change::RemoveSubject { name: subject.to_string() }];
+        changes.extend(self.remove_unused_schemas(&removed));
+
+        (removed.iter().map(|x| x.schema_id).collect(), changes)
+    }
+
+    pub fn config_set_subject(&self, subject_id: Option<i64>, config: &Config) -> Vec<Change> {
+        let subject = subject_id.and_then(|id| self.subjects.iter().find(|x| x.id == id)).map(|x| x.name.clone());
+        vec![Change::Config { subject, config: Box::new(config.clone()) }]
+    }
+
+    // the changes of Repository::config_delete_subject, with the config they remove
+    pub fn config_delete_subject(&self, subject_id: Option<i64>) -> (Option<Config>, Vec<Change>) {
+        match self.configs.iter().find(|x| x.subject_id == subject_id) {
+            Some(row) => {
+                let subject = subject_id.and_then(|id| self.subjects.iter().find(|x| x.id == id)).map(|x| x.name.clone());
+                (Some(row.config.clone()), vec![Change::RemoveConfig { subject }])
+            },
+            None => (None, vec![])
+        }
+    }
+
+    pub fn mode_set_subject(&self, subject_id: Option<i64>, mode: &Mode) -> Vec<Change> {
+        let subject = subject_id.and_then(|id| self.subjects.iter().find(|x| x.id == id)).map(|x| x.name.clone());
+        vec![Change::Mode { subject, mode: *mode }]
+    }
+
+    pub fn apply(&mut self, change: Change) {
+        match change {
+            Change::Subject { name, deleted } => match self.subjects.iter_mut().find(|x| x.name == name) {
+                Some(row) => row.deleted = deleted,
+                None => {
+                    self.last_subject_id += 1;
+                    self.subjects.push(SubjectRow { id: self.last_subject_id, name, deleted });
+                }
+            },
+            Change::Schema { id, fingerprint, lookup_fingerprint, schema, schema_type, references, deleted } => {
+                match self.schemas.iter_mut().find(|x| x.id == id) {
+                    Some(row) => {
+                        row.fingerprint = fingerprint;
+                        row.lookup_fingerprint = lookup_fingerprint;
+                        row.deleted = deleted;
+                    },
+                    None => self.schemas.push(SchemaRow { id, fingerprint, lookup_fingerprint, json: schema, schema_type, deleted })
+                }
+                self.last_schema_id = self.last_schema_id.max(id);
+
+                // references already stored for the schema are kept, like ON CONFLICT DO NOTHING
+                for reference in references {
+                    if !self.references.iter().any(|x| x.schema_id == id && x.reference.name == reference.name) {
+                        self.references.push(ReferenceRow { schema_id: id, reference });
+                    }
+                }
+            },
+            Change::Version { subject, version, id, deleted } => {
+                let subject_id = match self.subject(&subject) {
+                    Some(row) => row.id,
+                    None => return
+                };
+
+                match self.schema_versions.iter_mut().find(|x| x.subject_id == subject_id && x.version == version) {
+                    Some(row) => {
+                        row.schema_id = id;
+                        row.deleted = deleted;
+                    },
+                    None => self.schema_versions.push(SchemaVersionRow { version, subject_id, schema_id: id, deleted })
+                }
+            },
+            Change::RemoveVersion { subject, version } => {
+                if let Some(subject_id) = self.subject(&subject).map(|x| x.id) {
+                    self.schema_versions.retain(|x| !(x.subject_id == subject_id && x.version == version));
+                }
+            },
+            Change::RemoveSchema { id } => {
+                self.schemas.retain(|x| x.id != id);
+                self.references.retain(|x| x.schema_id != id);
+            },
+            Change::SchemaSequence { last_id } => self.last_schema_id = self.last_schema_id.max(last_id),
+            Change::RemoveSubject { name } => {
+                if let Some(subject_id) = self.subject(&name).map(|x| x.id) {
+                    self.subjects.retain(|x| x.id != subject_id);
+                    self.schema_versions.retain(|x| x.subject_id != subject_id);
+                    self.configs.retain(|x| x.subject_id != Some(subject_id));
+                    self.modes.retain(|x| x.subject_id != Some(subject_id));
+                }
+            },
+            Change::Config { subject, config } => {
+                // configs outlive their subject, so a config can name a subject no other change created
+                if let Some(name) = subject.as_ref().filter(|x| self.subject(x).is_none()) {
+                    self.apply(Change::Subject { name: name.clone(), deleted: true });
+                }
+                let subject_id = subject.and_then(|x| self.subject(&x).map(|x| x.id));
+
+                match self.configs.iter_mut().find(|x| x.subject_id == subject_id) {
+                    Some(row) => row.config = *config,
+                    None => self.configs.push(ConfigRow { subject_id, config: *config })
+                }
+            },
+            Change::Mode { subject, mode } => {
+                if let Some(name) = subject.as_ref().filter(|x| self.subject(x).is_none()) {
+                    self.apply(Change::Subject { name: name.clone(), deleted: true });
+                }
+                let subject_id = subject.and_then(|x| self.subject(&x).map(|x| x.id));
+
+                match self.modes.iter_mut().find(|x| x.subject_id == subject_id) {
+                    Some(row) => row.mode = mode,
+                    None => self.modes.push(ModeRow { subject_id, mode })
+                }
+            },
+            Change::RemoveConfig { subject } => {
+                // a subject that is gone took its config along, which leaves the global one alone
+                let subject_id = match subject {
+                    Some(name) => match self.subject(&name) {
+                        Some(row) => Some(row.id),
+                        None => return
+                    },
+                    None => None
+                };
+                self.configs.retain(|x| x.subject_id != subject_id);
+            }
+        }
+    }
+
+    // the fewest changes that rebuild these tables, subjects and schemas come before the versions, configs and modes naming them
+    pub fn snapshot(&self) -> Vec<Change> {
+        let subject_name = |id: i64| self.subjects.iter().find(|x| x.id == id).map(|x| x.name.clone());
+
+        let mut versions: Vec<&SchemaVersionRow> = self.schema_versions.iter().collect();
+        versions.sort_by_key(|x| (x.subject_id, x.version));
+
+        let subjects = self.subjects.iter().map(|x| Change::Subject { name: x.name.clone(), deleted: x.deleted });
+        let schemas = self.schemas.iter().map(|x| self.schema_change(x, x.deleted));
+        let versions = versions.into_iter().filter_map(|x| subject_name(x.subject_id).map(|subject| Change::Version { subject, version: x.version, id: x.schema_id, deleted: x.deleted }));
+        let configs = self.configs.iter().map(|x| Change::Config { subject: x.subject_id.and_then(subject_name), config: Box::new(x.config.clone()) });
+        let modes = self.modes.iter().map(|x| Change::Mode { subject: x.subject_id.and_then(subject_name), mode: x.mode });
+
+        let sequence = std::iter::once(Change::SchemaSequence { last_id: self.last_schema_id });
+
+        sequence.chain(subjects).chain(schemas).chain(versions).chain(configs).chain(modes).collect()
+    }
+}
+
+// keeps every table in memory with the semantics of PgRepository, for tests and local development
+#[derive(Clone, Default)]
+pub struct InMemoryRepository {
+    tables: Arc<Mutex<Tables>>
+}
+
+impl InMemoryRepository {
+    pub fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // OFFSET and LIMIT of the sql repositories
+    fn page<T>(rows: Vec<T>, offset: i64, limit: Option<i64>) -> Vec<T> {
+        rows.into_iter().skip(offset as usize).take(limit.map(|x| x as usize).unwrap_or(usize::MAX)).collect()
+    }
+
+    fn apply(tables: &mut Tables, changes: Vec<Change>) {
+        for change in changes {
+            tables.apply(change);
+        }
+    }
+}
+
+#[async_trait]
+impl Repository for InMemoryRepository {
+
+    async fn schema_find_by_id(&self, id: i64) -> Result<Option<SchemaPayload>, Error> {
+        let tables = self.tables();
+
+        let res = tables.schemas.iter().find(|x| x.id == id).map(|x| SchemaPayload {
+            schema: x.json.clone(),
+            schema_type: x.schema_type.clone(),
+            references: tables.schema_references(id),
+            id: None,
+            version: None
+        });
+
+        Ok(res)
+    }
+
+    async fn schema_soft_delete(&self, subject: &str, schema_id: i64) -> Result<u64, Error> {
+        let mut tables = self.tables();
+        let (affected, changes) = tables.schema_soft_delete(subject, schema_id);
+        Self::apply(&mut tables, changes);
+
+        Ok(affected)
+    }
+
+    async fn subject_soft_delete(&self, subject_name: &str) -> Result<Vec<i64>, Error> {
+        let mut tables = self.tables();
+        let (schema_ids, changes) = tables.subject_soft_delete(subject_name);
+        Self::apply(&mut tables, changes);
+
+        Ok(schema_ids)
+    }
+
+    async fn subject_deleted(&self, subject: &str) -> Result<Option<bool>, Error> {
+        Ok(self.tables().subject(subject).map(|x| x.deleted))
+    }
+
+    async fn schema_version_deleted(&self, subject: &str, version: i32) -> Result<Option<bool>, Error> {
+        let tables = self.tables();
+
+        let res = tables.subject(subject).and_then(|subject| {
+            tables.schema_versions.iter().find(|x| x.subject_id == subject.id && x.version == version).map(|x| x.deleted)
+        });
+
+        Ok(res)
+    }
+
+    async fn schema_permanent_delete(&self, subject: &str, version: i32) -> Result<u64, Error> {
+        let mut tables = self.tables();
+        let (affected, changes) = tables.schema_permanent_delete(subject, version);
+        Self::apply(&mut tables, changes);
+
+        Ok(affected)
+    }
+
+    async fn subject_permanent_delete(&self, subject: &str) -> Result<Vec<i64>, Error> {
+        let mut tables = self.tables();
+        let (schema_ids, changes) = tables.subject_permanent_delete(subject);
+        Self::apply(&mut tables, changes);
+
+        Ok(schema_ids)
+    }
+
+    async fn schema_undelete(&self, subject: &str, version: i32) -> Result<u64, Error> {
+        let mut tables = self.tables();
+        let (affected, changes) = tables.schema_undelete(subject, version);
+        Self::apply(&mut tables, changes);
+
+        Ok(affected)
+    }
+
+    async fn subject_undelete(&self, subject: &str) -> Result<Vec<i64>, Error> {
+        let mut tables = self.tables();
+        let (schema_ids, changes) = tables.subject_undelete(subject);
+        Self::apply(&mut tables, changes);
+
+        Ok(schema_ids)
+    }
+
+    async fn schema_find_by_version(&self, subject: &str, version: i32, deleted: bool) -> Result<Option<SubjectSchema>, Error> {
+        let tables = self.tables();
+
+        let res = tables.versions(subject, deleted)
+            .into_iter()
+            .find(|(x, _)| x.version == version)
+            .map(|(x, schema)| Tables::subject_schema(subject, x, schema));
+
+        Ok(res)
+    }
+
+    async fn schema_find_by_schema(&self, subject: &str, fingerprint: &str, normalize: bool) -> Result<Option<SubjectSchema>, Error> {
+        let tables = self.tables();
+
+        let res = tables.live_versions(subject)
+            .into_iter()
+            .filter(|(_, schema)| match normalize {
+                true => schema.lookup_fingerprint.as_deref() == Some(fingerprint),
+                false => schema.fingerprint == fingerprint
+            })
+            .min_by_key(|(x, _)| x.version)
+            .map(|(x, schema)| Tables::subject_schema(subject, x, schema));
+
+        Ok(res)
+    }
+
+    async fn insert(&self, subject: &str, fingerprints: &Fingerprints, schema: &str, schema_type: &SchemaType, references: &[SchemaReference], max_version: i32) -> Result<Option<i64>, Error> {
+        // the lock is held for the whole insert, which serializes version allocation like the subject row lock does
+        let mut tables = self.tables();
+
+        match tables.insert(subject, fingerprints, schema, schema_type, references, max_version) {
+            Some((schema_id, changes)) => {
+                Self::apply(&mut tables, changes);
+                Ok(Some(schema_id))
+            },
+            None => Ok(None)
+        }
+    }
+
+    async fn schema_import(&self, subject: &str, version: i32, id: i64, fingerprints: &Fingerprints, payload: &SchemaPayload) -> Result<SchemaImport, Error> {
+        let mut tables = self.tables();
+        let (res, changes) = tables.schema_import(subject, version, id, fingerprints, payload);
+        Self::apply(&mut tables, changes);
+
+        Ok(res)
+    }
+
+    async fn schema_fingerprints(&self) -> Result<Vec<StoredFingerprints>, Error> {
+        let res = self.tables().schemas
+            .iter()
+            .map(|x| StoredFingerprints { id: x.id, fingerprint: x.fingerprint.clone(), lookup_fingerprint: x.lookup_fingerprint.clone() })
+            .collect();
+
+        Ok(res)
+    }
+
+    async fn schema_fingerprints_update(&self, id: i64, fingerprints: &Fingerprints) -> Result<(), Error> {
+        let mut tables = self.tables();
+        let changes = tables.schema_fingerprints_update(id, fingerprints);
+        Self::apply(&mut tables, changes);
+
+        Ok(())
+    }
+
+    async fn schema_references(&self, schema_id: i64) -> Result<Vec<SchemaReference>, Error> {
+        Ok(self.tables().schema_references(schema_id))
+    }
+
+    async fn schema_subjects(&self, schema_id: i64, deleted: bool) -> Result<Vec<String>, Error> {
+        let mut res: Vec<String> = self.schema_versions(schema_id, deleted).await?.into_iter().map(|x| x.subject).collect();
+        res.dedup();
+
+        Ok(res)
+    }
+
+    async fn schema_versions(&self, schema_id: i64, deleted: bool) -> Result<Vec<SubjectVersion>, Error> {
+        let tables = self.tables();
+
+        let mut res: Vec<SubjectVersion> = tables.schema_versions
+            .iter()
+            .filter(|x| x.schema_id == schema_id && (deleted || !x.deleted))
+            .filter_map(|x| tables.subjects.iter().find(|s| s.id == x.subject_id && (deleted || !s.deleted)).map(|s| SubjectVersion { subject: s.name.clone(), version: x.version }))
+            .collect();
+        res.sort_by(|a, b| (&a.subject, a.version).cmp(&(&b.subject, b.version)));
+
+        Ok(res)
+    }
+
+    async fn schema_all(&self, subject_prefix: &str, latest_only: bool, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<SubjectSchema>, Error> {
+        let tables = self.tables();
+
+        let mut subjects: Vec<&str> = tables.subjects.iter().filter(|x| x.name.starts_with(subject_prefix)).map(|x| x.name.as_str()).collect();
+        subjects.sort();
+
+        let res: Vec<SubjectSchema> = subjects.into_iter()
+            .flat_map(|subject| {
+                let mut versions = tables.versions(subject, deleted);
+                versions.sort_by_key(|(x, _)| x.version);
+                if latest_only {
+                    versions = versions.split_off(versions.len().saturating_sub(1));
+                }
+                versions.into_iter().map(move |(x, schema)| Tables::subject_schema(subject, x, schema))
+            })
+            .collect();
+
+        Ok(Self::page(res, offset, limit))
+    }
+
+    async fn schema_referenced_by(&self, subject: &str, version: i32) -> Result<Vec<i64>, Error> {
+        let tables = self.tables();
+
+        let is_live = |schema_id: i64| tables.live_schema(schema_id).is_some() && tables.schema_versions.iter().any(|x| {
+            x.schema_id == schema_id && !x.deleted && tables.subjects.iter().any(|s| s.id == x.subject_id && !s.deleted)
+        });
+
+        let mut res: Vec<i64> = tables.references
+            .iter()
+            .filter(|x| x.reference.subject == subject && x.reference.version == version && is_live(x.schema_id))
+            .map(|x| x.schema_id)
+            .collect();
+        res.sort();
+        res.dedup();
+
+        Ok(res)
+    }
+
+    async fn subject_versions(&self, subject: &str, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<i32>, Error> {
+        let tables = self.tables();
+
+        let mut res: Vec<i32> = match tables.subject(subject).filter(|x| deleted || !x.deleted) {
+            Some(row) => tables.schema_versions.iter().filter(|x| x.subject_id == row.id && (deleted || !x.deleted)).map(|x| x.version).collect(),
+            None => vec![]
+        };
+        res.sort();
+
+        Ok(Self::page(res, offset, limit))
+    }
+
+    async fn subject_find(&self, subject: &str) -> Result<Option<Subject>, Error> {
+        let tables = self.tables();
+        Ok(tables.live_subject(subject).map(|x| Subject { id: x.id, name: x.name.clone() }))
+    }
+
+    async fn subject_find_deleted(&self, subject: &str) -> Result<Option<Subject>, Error> {
+        let tables = self.tables();
+        Ok(tables.subject(subject).map(|x| Subject { id: x.id, name: x.name.clone() }))
+    }
+
+    async fn subject_reserve(&self, subject: &str) -> Result<Subject, Error> {
+        let mut tables = self.tables();
+        let (res, changes) = tables.subject_reserve(subject);
+        Self::apply(&mut tables, changes);
+
+        Ok(res)
+    }
+
+    async fn subject_all(&self, subject_prefix: &str, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<Subject>, Error> {
+        let tables = self.tables();
+
+        let mut res: Vec<Subject> = tables.subjects
+            .iter()
+            .filter(|x| (deleted || !x.deleted) && x.name.starts_with(subject_prefix))
+            .map(|x| Subject { id: x.id, name: x.name.clone() })
+            .collect();
+        res.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self::page(res, offset, limit))
+    }
+
+    async fn subject_schemas(&self, subject: &str) -> Result<Vec<VersionedSchema>, Error> {
+        let tables = self.tables();
+
+        let mut res: Vec<VersionedSchema> = tables.live_versions(subject)
+            .into_iter()
+            .map(|(x, schema)| VersionedSchema { version: x.version, id: schema.id, schema: schema.json.clone(), schema_type: schema.schema_type.clone() })
+            .collect();
+        res.sort_by_key(|x| std::cmp::Reverse(x.version));
+
+        Ok(res)
+    }
+
+    async fn config_get_subject(&self, subject_id: Option<i64>) -> Result<Option<Config>, Error> {
+        let tables = self.tables();
+
+        let res = tables.configs
+            .iter()
+            .find(|x| x.subject_id == subject_id)
+            .map(|x| x.config.clone());
+
+        Ok(res)
+    }
+
+    async fn config_set_subject(&self, subject_id: Option<i64>, config: &Config) -> Result<(), Error> {
+        let mut tables = self.tables();
+        let changes = tables.config_set_subject(subject_id, config);
+        Self::apply(&mut tables, changes);
+
+        Ok(())
+    }
+
+    async fn config_delete_subject(&self, subject_id: Option<i64>) -> Result<Option<Config>, Error> {
+        let mut tables = self.tables();
+        let (res, changes) = tables.config_delete_subject(subject_id);
+        Self::apply(&mut tables, changes);
+
+        Ok(res)
+    }
+
+    async fn max_version(&self, subject: &str, deleted: bool) -> Result<Option<MaxVersion>, Error> {
+        Ok(Some(MaxVersion { max_version: self.tables().max_version(subject, deleted) }))
+    }
+
+    async fn mode_get_subject(&self, subject_id: Option<i64>) -> Result<Option<Mode>, Error> {
+        Ok(self.tables().modes.iter().find(|x| x.subject_id == subject_id).map(|x| x.mode))
+    }
+
+    async fn mode_set_subject(&self, subject_id: Option<i64>, mode: &Mode) -> Result<(), Error> {
+        let mut tables = self.tables();
+        let changes = tables.mode_set_subject(subject_id, mode);
+        Self::apply(&mut tables, changes);
+
+        Ok(())
+    }
+}
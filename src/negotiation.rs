@@ -0,0 +1,49 @@
+use axum::http::{header, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::error::AppError;
+
+// the media types of Confluent's registry, JSON bodies are sent as the first unless the client asks for plain application/json
+const SCHEMA_REGISTRY_V1_JSON: &str = "application/vnd.schemaregistry.v1+json";
+const SCHEMA_REGISTRY_JSON: &str = "application/vnd.schemaregistry+json";
+const JSON: &str = "application/json";
+
+// the best media type of an Accept header by quality then order, None when it accepts none of them
+fn preferred(accept: &str) -> Option<&'static str> {
+    let mut ranges: Vec<(&str, f32)> = accept.split(',')
+        .map(|range| {
+            let mut parts = range.split(';').map(|x| x.trim());
+            let media_type = parts.next().unwrap_or_default();
+            let quality = parts.find_map(|x| x.strip_prefix("q=")).and_then(|x| x.parse::<f32>().ok()).unwrap_or(1.0);
+            (media_type, quality)
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges.iter().find_map(|(media_type, _)| match media_type.to_ascii_lowercase().as_str() {
+        SCHEMA_REGISTRY_V1_JSON | SCHEMA_REGISTRY_JSON | "application/*" | "*/*" => Some(SCHEMA_REGISTRY_V1_JSON),
+        JSON => Some(JSON),
+        _ => None
+    })
+}
+
+// rejects requests accepting none of the media types before they run, and labels JSON responses with the negotiated one
+pub async fn negotiate<B>(request: Request<B>, next: Next<B>) -> Response {
+    let accept = request.headers().get(header::ACCEPT).map(|x| x.to_str().unwrap_or_default()).filter(|x| !x.trim().is_empty());
+    let media_type = match accept {
+        None => SCHEMA_REGISTRY_V1_JSON,
+        Some(accept) => match preferred(accept) {
+            Some(media_type) => media_type,
+            None => return AppError::InvalidRequest(StatusCode::NOT_ACCEPTABLE, String::from("HTTP 406 Not Acceptable")).into_response()
+        }
+    };
+
+    let mut response = next.run(request).await;
+    if response.headers().get(header::CONTENT_TYPE).is_some_and(|x| x == JSON) {
+        response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(media_type));
+    }
+
+    response
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::fs;
+
+use protobuf::descriptor::{DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorProto};
+use protobuf::descriptor::field_descriptor_proto::{Label, Type};
+use protobuf::text_format;
+use protobuf_parse::Parser;
+use crate::schema::{Incompatibility, InvalidSchema, ResolvedReference, SchemaProvider};
+
+const SCHEMA_FILE: &str = "schema.proto";
+
+pub struct ProtobufSchema {
+    pub file: FileDescriptorProto
+}
+
+impl ProtobufSchema {
+    // the pure parser only reads from disk and takes no resolver of its own, so the schema and its imports are
+    // staged in a scratch directory, which is why Service::parse runs this off the async workers
+    pub fn parse_str(input: &str, references: &[ResolvedReference]) -> Result<ProtobufSchema, InvalidSchema> {
+        let dir = tempfile::tempdir().map_err(|e| InvalidSchema(e.to_string()))?;
+
+        for reference in references {
+            let path = dir.path().join(&reference.name);
+            if !path.starts_with(dir.path()) || reference.name.contains("..") || reference.name == SCHEMA_FILE {
+                return Err(InvalidSchema(format!("reference name {} is not a valid import path", reference.name)))
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| InvalidSchema(e.to_string()))?;
+            }
+            fs::write(&path, &reference.schema).map_err(|e| InvalidSchema(e.to_string()))?;
+        }
+
+        let path = dir.path().join(SCHEMA_FILE);
+        fs::write(&path, input).map_err(|e| InvalidSchema(e.to_string()))?;
+
+        let parsed = Parser::new()
+            .pure()
+            .include(dir.path())
+            .input(&path)
+            .parse_and_typecheck()
+            .map_err(|e| InvalidSchema(format!("{:#}", e).replace(&format!("{}/", dir.path().display()), "").replace(&dir.path().display().to_string(), ".")))?;
+
+        let file = parsed.file_descriptors
+            .into_iter()
+            .find(|x| x.name() == SCHEMA_FILE)
+            .ok_or(InvalidSchema(String::from("schema.proto was not parsed")))?;
+
+        Ok(ProtobufSchema { file })
+    }
+
+    pub fn canonical_form(&self) -> String {
+        text_format::print_to_string(&self.file)
+    }
+
+    fn messages(&self) -> HashMap<String, &DescriptorProto> {
+        let mut res = HashMap::new();
+        for message in &self.file.message_type {
+            collect_messages("", message, &mut res);
+        }
+        res
+    }
+
+    fn enums(&self) -> HashMap<String, &EnumDescriptorProto> {
+        let mut res: HashMap<String, &EnumDescriptorProto> = self.file.enum_type.iter().map(|x| (x.name().to_string(), x)).collect();
+        for (name, message) in self.messages() {
+            res.extend(message.enum_type.iter().map(|x| (format!("{}.{}", name, x.name()), x)));
+        }
+        res
+    }
+
+    fn type_name(&self, field: &FieldDescriptorProto) -> String {
+        let name = field.type_name().trim_start_matches('.');
+        let package = self.file.package();
+
+        match name.strip_prefix(package).and_then(|x| x.strip_prefix('.')) {
+            Some(relative) if !package.is_empty() => relative.to_string(),
+            _ => name.to_string()
+        }
+    }
+}
+
+fn collect_messages<'a>(prefix: &str, message: &'a DescriptorProto, res: &mut HashMap<String, &'a DescriptorProto>) {
+    let name = if prefix.is_empty() { message.name().to_string() } else { format!("{}.{}", prefix, message.name()) };
+
+    for nested in &message.nested_type {
+        collect_messages(&name, nested, res);
+    }
+
+    res.insert(name, message);
+}
+
+// proto3 `optional` fields live in a synthetic oneof which has no wire meaning
+fn real_oneof<'a>(message: &'a DescriptorProto, field: &FieldDescriptorProto) -> Option<&'a str> {
+    if field.has_oneof_index() && !field.proto3_optional() {
+        message.oneof_decl.get(field.oneof_index() as usize).map(|x| x.name())
+    } else {
+        None
+    }
+}
+
+#[derive(PartialEq)]
+enum WireKind {
+    Varint,
+    ZigZag,
+    Fixed32,
+    Fixed64,
+    Float,
+    Double,
+    LengthDelimited,
+    Message,
+    Enum,
+    Group
+}
+
+fn wire_kind(field: &FieldDescriptorProto) -> WireKind {
+    match field.type_() {
+        Type::TYPE_INT32 | Type::TYPE_INT64 | Type::TYPE_UINT32 | Type::TYPE_UINT64 | Type::TYPE_BOOL => WireKind::Varint,
+        Type::TYPE_SINT32 | Type::TYPE_SINT64 => WireKind::ZigZag,
+        Type::TYPE_FIXED32 | Type::TYPE_SFIXED32 => WireKind::Fixed32,
+        Type::TYPE_FIXED64 | Type::TYPE_SFIXED64 => WireKind::Fixed64,
+        Type::TYPE_FLOAT => WireKind::Float,
+        Type::TYPE_DOUBLE => WireKind::Double,
+        Type::TYPE_STRING | Type::TYPE_BYTES => WireKind::LengthDelimited,
+        Type::TYPE_MESSAGE => WireKind::Message,
+        Type::TYPE_ENUM => WireKind::Enum,
+        Type::TYPE_GROUP => WireKind::Group
+    }
+}
+
+fn type_description(schema: &ProtobufSchema, field: &FieldDescriptorProto) -> String {
+    let label = match field.label() {
+        Label::LABEL_REPEATED => "repeated ",
+        Label::LABEL_REQUIRED => "required ",
+        _ => ""
+    };
+
+    match wire_kind(field) {
+        WireKind::Message | WireKind::Enum | WireKind::Group => format!("{}{}", label, schema.type_name(field)),
+        _ => format!("{}{}", label, format!("{:?}", field.type_()).trim_start_matches("TYPE_").to_lowercase())
+    }
+}
+
+pub struct ProtobufSchemaCompatibility;
+
+impl ProtobufSchemaCompatibility {
+    pub fn incompatibilities(writers_schema: &ProtobufSchema, readers_schema: &ProtobufSchema) -> Vec<Incompatibility> {
+        let readers_messages = readers_schema.messages();
+        let mut res = vec![];
+
+        let mut writers_messages: Vec<(String, &DescriptorProto)> = writers_schema.messages().into_iter().collect();
+        writers_messages.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (name, writers_message) in writers_messages {
+            match readers_messages.get(&name) {
+                Some(readers_message) => Self::message_incompatibilities(&name, writers_schema, writers_message, readers_schema, readers_message, &mut res),
+                None => res.push(Incompatibility::new(&name, "the message is missing in the reader", "missing", &name))
+            }
+        }
+
+        let readers_enums = readers_schema.enums();
+        let mut writers_enums: Vec<(String, &EnumDescriptorProto)> = writers_schema.enums().into_iter().collect();
+        writers_enums.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (name, writers_enum) in writers_enums {
+            match readers_enums.get(&name) {
+                Some(readers_enum) => Self::enum_incompatibilities(&name, writers_enum, readers_enum, &mut res),
+                None => res.push(Incompatibility::new(&name, "the enum is missing in the reader", "missing", &name))
+            }
+        }
+
+        res
+    }
+
+    // a value the reader does not know is read as unrecognized, and a renumbered one as another value
+    fn enum_incompatibilities(name: &str, writers_enum: &EnumDescriptorProto, readers_enum: &EnumDescriptorProto, res: &mut Vec<Incompatibility>) {
+        for writers_value in &writers_enum.value {
+            let path = format!("{}.{}", name, writers_value.name());
+            let writer_type = writers_value.number().to_string();
+
+            match readers_enum.value.iter().find(|x| x.name() == writers_value.name()) {
+                Some(readers_value) if readers_value.number() != writers_value.number() =>
+                    res.push(Incompatibility::new(&path, "the enum value was renumbered", &readers_value.number().to_string(), &writer_type)),
+                Some(_) => (),
+                None => res.push(Incompatibility::new(&path, "the enum value was removed", "missing", &writer_type))
+            }
+        }
+    }
+
+    fn message_incompatibilities(name: &str, writers_schema: &ProtobufSchema, writers_message: &DescriptorProto, readers_schema: &ProtobufSchema, readers_message: &DescriptorProto, res: &mut Vec<Incompatibility>) {
+        let readers_fields: HashMap<i32, &FieldDescriptorProto> = readers_message.field.iter().map(|x| (x.number(), x)).collect();
+        let writers_fields: HashMap<i32, &FieldDescriptorProto> = writers_message.field.iter().map(|x| (x.number(), x)).collect();
+
+        for writers_field in &writers_message.field {
+            let path = format!("{}.{}", name, writers_field.name());
+            let writer_type = type_description(writers_schema, writers_field);
+
+            match readers_fields.get(&writers_field.number()) {
+                Some(readers_field) => {
+                    let path = format!("{}.{}", name, readers_field.name());
+                    let reader_type = type_description(readers_schema, readers_field);
+
+                    if let Some(reason) = Self::field_incompatibility(writers_schema, writers_field, readers_schema, readers_field) {
+                        res.push(Incompatibility::new(&path, reason, &reader_type, &writer_type));
+                    }
+
+                    if writers_field.name() != readers_field.name() {
+                        res.push(Incompatibility::new(&path, "the field number was reused by a field of another name", &reader_type, &writer_type));
+                    }
+
+                    let writers_oneof = real_oneof(writers_message, writers_field);
+                    let readers_oneof = real_oneof(readers_message, readers_field);
+
+                    // moving a plain field into a oneof that already existed breaks existing writers
+                    if let (None, Some(oneof)) = (writers_oneof, readers_oneof) {
+                        if writers_message.oneof_decl.iter().any(|x| x.name() == oneof) {
+                            res.push(Incompatibility::new(&path, "the field moved into an existing oneof", &reader_type, &writer_type));
+                        }
+                    }
+                },
+                None => {
+                    if writers_field.label() == Label::LABEL_REQUIRED {
+                        res.push(Incompatibility::new(&path, "a required field was removed", "missing", &writer_type));
+                    } else if real_oneof(writers_message, writers_field).is_some() {
+                        res.push(Incompatibility::new(&path, "a oneof field was removed", "missing", &writer_type));
+                    }
+                }
+            }
+        }
+
+        for readers_field in &readers_message.field {
+            if !writers_fields.contains_key(&readers_field.number()) && readers_field.label() == Label::LABEL_REQUIRED {
+                let path = format!("{}.{}", name, readers_field.name());
+                res.push(Incompatibility::new(&path, "a required field was added", &type_description(readers_schema, readers_field), "missing"));
+            }
+        }
+    }
+
+    fn field_incompatibility(writers_schema: &ProtobufSchema, writers_field: &FieldDescriptorProto, readers_schema: &ProtobufSchema, readers_field: &FieldDescriptorProto) -> Option<&'static str> {
+        let writers_repeated = writers_field.label() == Label::LABEL_REPEATED;
+        let readers_repeated = readers_field.label() == Label::LABEL_REPEATED;
+
+        if writers_repeated != readers_repeated {
+            return Some("the field changed between singular and repeated")
+        }
+
+        let kind = wire_kind(writers_field);
+
+        if kind != wire_kind(readers_field) {
+            return Some("the field's type has another wire encoding")
+        }
+
+        match kind {
+            WireKind::Message | WireKind::Enum | WireKind::Group
+                if writers_schema.type_name(writers_field) != readers_schema.type_name(readers_field) =>
+                    Some("the field's type name changed"),
+            _ => None
+        }
+    }
+}
+
+pub struct ProtobufSchemaProvider;
+
+impl SchemaProvider for ProtobufSchemaProvider {
+    type Schema = ProtobufSchema;
+
+    fn schema_type(&self) -> &'static str {
+        "PROTOBUF"
+    }
+
+    fn parse(&self, schema: &str, references: &[ResolvedReference]) -> Result<ProtobufSchema, InvalidSchema> {
+        ProtobufSchema::parse_str(schema, references)
+    }
+
+    fn canonical_form(&self, schema: &ProtobufSchema) -> String {
+        schema.canonical_form()
+    }
+
+    // the canonical form is the text format of the descriptor, not a .proto file, so the schema is stored as sent
+    fn normalized_schema(&self, _schema: &ProtobufSchema) -> Option<String> {
+        None
+    }
+
+    fn incompatibilities(&self, writers_schema: &ProtobufSchema, readers_schema: &ProtobufSchema) -> Vec<Incompatibility> {
+        ProtobufSchemaCompatibility::incompatibilities(writers_schema, readers_schema)
+    }
+
+    // the well-known types come with the parser rather than from a reference
+    fn references(&self, schema: &ProtobufSchema) -> Vec<String> {
+        schema.file.dependency.iter().filter(|x| !x.starts_with("google/protobuf/")).cloned().collect()
+    }
+}
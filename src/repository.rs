@@ -1,50 +1,144 @@
 use async_trait::async_trait;
-use sqlx::{Error, PgPool};
+use sqlx::{Error, PgConnection, PgPool};
 
 use crate::data::*;
 
 #[async_trait]
 pub trait Repository {
     async fn schema_find_by_id(&self, id: i64) -> Result<Option<SchemaPayload>, Error>;
-    async fn schema_soft_delete(&self, schema_id: i64) -> Result<u64, Error>;
-    async fn subject_soft_delete(&self, subject_name: &String) -> Result<Vec<i64>, Error>;
-    async fn schema_find_by_version(&self, subject: &String, version: i32) -> Result<Option<FindBySchemaResponse>, Error>;
-    async fn schema_find_by_schema(&self, subject: &String, fingerprint: &String) -> Result<Option<FindBySchemaResponse>, Error>;
-    async fn insert(&self, fingerprint: &String, schema: &String, subject_id: i64, max_version: i32) -> Result<i64, Error>;
-    async fn insert_schema(&self, fingerprint: &String, schema: &String) -> Result<i64, Error>;
-    async fn insert_schema_version(&self, max_version: i32, subject_id: i64, schema_id: i64) -> Result<(), Error>;
-    async fn subject_versions(&self, subject: &String) -> Result<Vec<i32>, Error>;
-    async fn subject_find(&self, subject: &String) -> Result<Option<Subject>, Error>;
-    async fn subject_all(&self) -> Result<Vec<Subject>, Error>;
-    async fn subject_schemas(&self, subject: &String) -> Result<Vec<VersionedSchema>, Error>;
-    async fn config_get_subject(&self, subject_id: Option<i64>) -> Result<Option<SchemaCompatibility>, Error>;
-    async fn config_set_subject(&self, subject_id: Option<i64>, compatibility: &Compatibility) -> Result<(), Error>;
-    async fn max_version(&self, subject: &String) -> Result<Option<MaxVersion>, Error>;
+    async fn schema_soft_delete(&self, subject: &str, schema_id: i64) -> Result<u64, Error>;
+    async fn subject_soft_delete(&self, subject_name: &str) -> Result<Vec<i64>, Error>;
+    // whether the subject or version was soft deleted, None when it doesn't exist at all
+    async fn subject_deleted(&self, subject: &str) -> Result<Option<bool>, Error>;
+    async fn schema_version_deleted(&self, subject: &str, version: i32) -> Result<Option<bool>, Error>;
+    // removes a soft deleted version, and its schema once no version of any subject uses it
+    async fn schema_permanent_delete(&self, subject: &str, version: i32) -> Result<u64, Error>;
+    // removes a soft deleted subject with its versions, configs and modes, and the schemas only it used
+    async fn subject_permanent_delete(&self, subject: &str) -> Result<Vec<i64>, Error>;
+    // restores a soft deleted version under its number, reviving its subject and schema
+    async fn schema_undelete(&self, subject: &str, version: i32) -> Result<u64, Error>;
+    // restores a subject with its soft deleted versions, returning the ids of their schemas
+    async fn subject_undelete(&self, subject: &str) -> Result<Vec<i64>, Error>;
+    // `deleted` includes soft deleted subjects, versions and schemas
+    async fn schema_find_by_version(&self, subject: &str, version: i32, deleted: bool) -> Result<Option<SubjectSchema>, Error>;
+    // with `normalize` the lookup fingerprints are compared, which several versions can share, the first of them is returned
+    async fn schema_find_by_schema(&self, subject: &str, fingerprint: &str, normalize: bool) -> Result<Option<SubjectSchema>, Error>;
+    // stores the schema as version `max_version` + 1, returns None when the subject moved past `max_version` in the meantime
+    async fn insert(&self, subject: &str, fingerprints: &Fingerprints, schema: &str, schema_type: &SchemaType, references: &[SchemaReference], max_version: i32) -> Result<Option<i64>, Error>;
+    // stores the schema under the given id and subject version, checking in the same transaction that neither holds another schema
+    async fn schema_import(&self, subject: &str, version: i32, id: i64, fingerprints: &Fingerprints, payload: &SchemaPayload) -> Result<SchemaImport, Error>;
+    // the fingerprints of every schema, soft deleted ones too
+    async fn schema_fingerprints(&self) -> Result<Vec<StoredFingerprints>, Error>;
+    // keeps the fingerprint when another schema is already stored under the new one
+    async fn schema_fingerprints_update(&self, id: i64, fingerprints: &Fingerprints) -> Result<(), Error>;
+    async fn schema_references(&self, schema_id: i64) -> Result<Vec<SchemaReference>, Error>;
+    // the subjects and versions registering the schema, ordered by subject and version
+    async fn schema_subjects(&self, schema_id: i64, deleted: bool) -> Result<Vec<String>, Error>;
+    async fn schema_versions(&self, schema_id: i64, deleted: bool) -> Result<Vec<SubjectVersion>, Error>;
+    // the versions of the subjects starting with `subject_prefix` ordered by subject and version, only the last of each subject with `latest_only`
+    async fn schema_all(&self, subject_prefix: &str, latest_only: bool, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<SubjectSchema>, Error>;
+    // ids of the live schemas that reference the given subject version
+    async fn schema_referenced_by(&self, subject: &str, version: i32) -> Result<Vec<i64>, Error>;
+    // listings are ordered by version and subject name, `offset` and `limit` page through them
+    async fn subject_versions(&self, subject: &str, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<i32>, Error>;
+    async fn subject_find(&self, subject: &str) -> Result<Option<Subject>, Error>;
+    // like subject_find, soft deleted subjects included
+    async fn subject_find_deleted(&self, subject: &str) -> Result<Option<Subject>, Error>;
+    // stores a subject that doesn't exist yet as soft deleted, so a config can name it before its first registration
+    async fn subject_reserve(&self, subject: &str) -> Result<Subject, Error>;
+    async fn subject_all(&self, subject_prefix: &str, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<Subject>, Error>;
+    async fn subject_schemas(&self, subject: &str) -> Result<Vec<VersionedSchema>, Error>;
+    async fn config_get_subject(&self, subject_id: Option<i64>) -> Result<Option<Config>, Error>;
+    // replaces the whole config, fields left unset are stored as null
+    async fn config_set_subject(&self, subject_id: Option<i64>, config: &Config) -> Result<(), Error>;
+    // returns the removed config, None when there was none
+    async fn config_delete_subject(&self, subject_id: Option<i64>) -> Result<Option<Config>, Error>;
+    // `deleted` counts soft deleted versions too, whose numbers are never handed out again
+    async fn max_version(&self, subject: &str, deleted: bool) -> Result<Option<MaxVersion>, Error>;
+    async fn mode_get_subject(&self, subject_id: Option<i64>) -> Result<Option<Mode>, Error>;
+    async fn mode_set_subject(&self, subject_id: Option<i64>, mode: &Mode) -> Result<(), Error>;
 }
 
 #[derive(Clone)]
 pub struct PgRepository { pub pool: PgPool }
 
+impl PgRepository {
+
+    async fn subject_upsert(conn: &mut PgConnection, subject: &str) -> Result<Subject, Error> {
+        sqlx::query_as!(Subject, r#"INSERT INTO subjects (name, created_at) VALUES ($1, now()) ON CONFLICT (name) DO UPDATE SET deleted_at = null RETURNING id, name"#, subject)
+            .fetch_one(conn)
+            .await
+    }
+
+    // an explicit id bypasses schemas_id_seq, which is then moved to the largest id so later registrations don't collide
+    async fn insert_schema(conn: &mut PgConnection, id: Option<i64>, fingerprints: &Fingerprints, schema: &str, schema_type: &SchemaType) -> Result<i64, Error> {
+        let id = match id {
+            Some(id) => {
+                let _ = sqlx::query!(r#"INSERT INTO schemas (id, fingerprint, lookup_fingerprint, json, schema_type, created_at) VALUES ($1, $2, $3, $4, $5, now()) ON CONFLICT (id) DO UPDATE SET deleted_at = null"#, id, fingerprints.fingerprint, fingerprints.lookup_fingerprint, schema, schema_type.as_str())
+                    .execute(&mut *conn)
+                    .await?;
+
+                let _ = sqlx::query!(r#"SELECT setval('schemas_id_seq', GREATEST((SELECT max(id) FROM schemas), $1))"#, id)
+                    .fetch_one(&mut *conn)
+                    .await?;
+
+                id
+            },
+            None => sqlx::query!(r#"INSERT INTO schemas (fingerprint, lookup_fingerprint, json, schema_type, created_at) VALUES ($1, $2, $3, $4, now()) ON CONFLICT (fingerprint) DO UPDATE SET deleted_at = null returning id;"#, fingerprints.fingerprint, fingerprints.lookup_fingerprint, schema, schema_type.as_str())
+                .fetch_one(&mut *conn)
+                .await?
+                .id
+        };
+
+        Ok(id)
+    }
+
+    async fn insert_schema_references(conn: &mut PgConnection, schema_id: i64, references: &[SchemaReference]) -> Result<(), Error> {
+        for reference in references {
+            let _ = sqlx::query!(r#"INSERT INTO schema_references (schema_id, name, subject, version) VALUES ($1, $2, $3, $4) ON CONFLICT (schema_id, name) DO NOTHING"#, schema_id, reference.name, reference.subject, reference.version)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // a version number that is taken, even by a soft deleted version, fails as a unique violation
+    async fn insert_schema_version(conn: &mut PgConnection, version: i32, subject_id: i64, schema_id: i64) -> Result<(), Error> {
+        let _ = sqlx::query!(r#"INSERT INTO schema_versions (version, subject_id, schema_id) VALUES ($1, $2, $3)"#, version, subject_id, schema_id).execute(conn).await?;
+
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl Repository for PgRepository {
 
     async fn schema_find_by_id(&self, id: i64) -> Result<Option<SchemaPayload>, Error> {
-        sqlx::query_as!(SchemaPayload, r#"select json as schema from schemas where id = $1;"#, id)
+        let res = sqlx::query!(r#"select json as schema, schema_type from schemas where id = $1;"#, id)
             .fetch_optional(&self.pool)
-            .await
+            .await?;
+
+        match res {
+            Some(record) => {
+                let references = self.schema_references(id).await?;
+                Ok(Some(SchemaPayload { schema: record.schema, schema_type: record.schema_type.into(), references, id: None, version: None }))
+            },
+            None => Ok(None)
+        }
     }
 
-    async fn schema_soft_delete(&self, schema_id: i64) -> Result<u64, Error> {
+    async fn schema_soft_delete(&self, subject: &str, schema_id: i64) -> Result<u64, Error> {
 
-        let tx = self.pool.begin().await?;
+        let mut tx = self.pool.begin().await?;
 
-        let affected = sqlx::query!(r#"UPDATE schemas SET deleted_at = now() WHERE id = $1"#, schema_id)
-            .execute(&self.pool)
+        let affected = sqlx::query!(r#"UPDATE schema_versions SET deleted_at = now() WHERE schema_id = $1 AND deleted_at IS NULL AND subject_id = (SELECT id FROM subjects WHERE name = $2)"#, schema_id, subject)
+            .execute(&mut *tx)
             .await?
             .rows_affected();
 
-        let _ = sqlx::query!(r#"DELETE FROM schema_versions WHERE schema_id = $1"#, schema_id)
-            .execute(&self.pool)
+        let _ = sqlx::query!(r#"UPDATE schemas SET deleted_at = now() WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM schema_versions WHERE schema_id = $1 AND deleted_at IS NULL)"#, schema_id)
+            .execute(&mut *tx)
             .await?
             .rows_affected();
 
@@ -53,17 +147,17 @@ impl Repository for PgRepository {
         Ok(affected)
     }
 
-    async fn subject_soft_delete(&self, subject_name: &String) -> Result<Vec<i64>, Error> {
-        let tx = self.pool.begin().await?;
+    async fn subject_soft_delete(&self, subject_name: &str) -> Result<Vec<i64>, Error> {
+        let mut tx = self.pool.begin().await?;
 
         let subject_record = sqlx::query!(r#"UPDATE subjects SET deleted_at = now() WHERE name = $1 RETURNING id"#, subject_name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
 
         let schema_ids = match subject_record {
             Some(subject) => {
-                let schema_ids = sqlx::query!(r#"DELETE FROM schema_versions WHERE subject_id = $1 RETURNING schema_id"#, subject.id)
-                    .fetch_all(&self.pool)
+                let schema_ids = sqlx::query!(r#"UPDATE schema_versions SET deleted_at = now() WHERE subject_id = $1 AND deleted_at IS NULL RETURNING schema_id"#, subject.id)
+                    .fetch_all(&mut *tx)
                     .await?
                     .iter()
                     .map(|x| x.schema_id)
@@ -79,45 +173,244 @@ impl Repository for PgRepository {
         schema_ids
     }
 
-    async fn schema_find_by_version(&self, subject: &String, version: i32) -> Result<Option<FindBySchemaResponse>, Error> {
-        sqlx::query_as!(FindBySchemaResponse, r#"select sub.name as name, sv.version as version, sch.id as id, sch.json as schema from schemas sch inner join schema_versions sv on sch.id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id where sub.deleted_at is null and sch.deleted_at is null and sv.version = $1 and sub.name = $2;"#, version, subject)
+    async fn subject_deleted(&self, subject: &str) -> Result<Option<bool>, Error> {
+        let res = sqlx::query!(r#"SELECT deleted_at IS NOT NULL as "deleted!" FROM subjects WHERE name = $1"#, subject)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(|x| x.deleted))
+    }
+
+    async fn schema_version_deleted(&self, subject: &str, version: i32) -> Result<Option<bool>, Error> {
+        let res = sqlx::query!(r#"SELECT sv.deleted_at IS NOT NULL as "deleted!" FROM schema_versions sv INNER JOIN subjects sub ON sv.subject_id = sub.id WHERE sub.name = $1 AND sv.version = $2"#, subject, version)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(|x| x.deleted))
+    }
+
+    async fn schema_permanent_delete(&self, subject: &str, version: i32) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let removed = sqlx::query!(r#"DELETE FROM schema_versions WHERE version = $1 AND deleted_at IS NOT NULL AND subject_id = (SELECT id FROM subjects WHERE name = $2) RETURNING schema_id"#, version, subject)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        // schemas still used by a version of any subject, soft deleted or not, are kept
+        let affected = match removed {
+            Some(record) => {
+                let _ = sqlx::query!(r#"DELETE FROM schemas WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM schema_versions WHERE schema_id = $1)"#, record.schema_id)
+                    .execute(&mut *tx)
+                    .await?;
+                1
+            },
+            None => 0
+        };
+
+        tx.commit().await?;
+
+        Ok(affected)
+    }
+
+    async fn subject_permanent_delete(&self, subject: &str) -> Result<Vec<i64>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let schema_ids: Vec<i64> = sqlx::query!(r#"DELETE FROM schema_versions WHERE subject_id = (SELECT id FROM subjects WHERE name = $1 AND deleted_at IS NOT NULL) RETURNING schema_id"#, subject)
+            .fetch_all(&mut *tx)
+            .await?
+            .iter()
+            .map(|x| x.schema_id)
+            .collect();
+
+        let _ = sqlx::query!(r#"DELETE FROM schemas WHERE id = ANY($1) AND NOT EXISTS (SELECT 1 FROM schema_versions sv WHERE sv.schema_id = schemas.id)"#, &schema_ids[..])
+            .execute(&mut *tx)
+            .await?;
+
+        // configs and modes go with the subject row
+        let _ = sqlx::query!(r#"DELETE FROM subjects WHERE name = $1 AND deleted_at IS NOT NULL"#, subject)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(schema_ids)
+    }
+
+    async fn schema_undelete(&self, subject: &str, version: i32) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let restored = sqlx::query!(r#"UPDATE schema_versions SET deleted_at = null WHERE version = $1 AND deleted_at IS NOT NULL AND subject_id = (SELECT id FROM subjects WHERE name = $2) RETURNING schema_id"#, version, subject)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let affected = match restored {
+            Some(record) => {
+                let _ = sqlx::query!(r#"UPDATE schemas SET deleted_at = null WHERE id = $1"#, record.schema_id)
+                    .execute(&mut *tx)
+                    .await?;
+                let _ = sqlx::query!(r#"UPDATE subjects SET deleted_at = null WHERE name = $1"#, subject)
+                    .execute(&mut *tx)
+                    .await?;
+                1
+            },
+            None => 0
+        };
+
+        tx.commit().await?;
+
+        Ok(affected)
+    }
+
+    async fn subject_undelete(&self, subject: &str) -> Result<Vec<i64>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let subject_record = sqlx::query!(r#"UPDATE subjects SET deleted_at = null WHERE name = $1 RETURNING id"#, subject)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let schema_ids = match subject_record {
+            Some(subject) => sqlx::query!(r#"UPDATE schema_versions SET deleted_at = null WHERE subject_id = $1 AND deleted_at IS NOT NULL RETURNING schema_id"#, subject.id)
+                .fetch_all(&mut *tx)
+                .await?
+                .iter()
+                .map(|x| x.schema_id)
+                .collect(),
+            None => vec![]
+        };
+
+        let _ = sqlx::query!(r#"UPDATE schemas SET deleted_at = null WHERE id = ANY($1)"#, &schema_ids[..])
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(schema_ids)
+    }
+
+    async fn schema_find_by_version(&self, subject: &str, version: i32, deleted: bool) -> Result<Option<SubjectSchema>, Error> {
+        sqlx::query_as!(SubjectSchema, r#"select sub.name as subject, sv.version as version, sch.id as id, sch.json as schema, sch.schema_type as schema_type from schemas sch inner join schema_versions sv on sch.id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id where ($3 or (sub.deleted_at is null and sch.deleted_at is null and sv.deleted_at is null)) and sv.version = $1 and sub.name = $2;"#, version, subject, deleted)
             .fetch_optional(&self.pool)
             .await
     }
 
-    async fn schema_find_by_schema(&self, subject: &String, fingerprint: &String) -> Result<Option<FindBySchemaResponse>, Error> {
-        sqlx::query_as!(FindBySchemaResponse, r#"select sub.name as name, sv.version as version, sch.id as id, sch.json as schema from schemas sch inner join schema_versions sv on sch.id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id where sub.deleted_at is null and sch.deleted_at is null and sch.fingerprint = $1 and sub.name = $2;"#, fingerprint, subject)
+    async fn schema_find_by_schema(&self, subject: &str, fingerprint: &str, normalize: bool) -> Result<Option<SubjectSchema>, Error> {
+        sqlx::query_as!(SubjectSchema, r#"select sub.name as subject, sv.version as version, sch.id as id, sch.json as schema, sch.schema_type as schema_type from schemas sch inner join schema_versions sv on sch.id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id where sub.deleted_at is null and sch.deleted_at is null and sv.deleted_at is null and (case when $3 then sch.lookup_fingerprint else sch.fingerprint end) = $1 and sub.name = $2 order by sv.version limit 1;"#, fingerprint, subject, normalize)
             .fetch_optional(&self.pool)
             .await
     }
 
-    async fn insert(&self, fingerprint: &String, schema: &String, subject_id: i64, max_version: i32) -> Result<i64, Error> {
-        let tx = self.pool.begin().await?;
+    async fn insert(&self, subject: &str, fingerprints: &Fingerprints, schema: &str, schema_type: &SchemaType, references: &[SchemaReference], max_version: i32) -> Result<Option<i64>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // the upsert keeps the subject row locked until commit, which serializes version allocation per subject
+        let subject_record = Self::subject_upsert(&mut tx, subject).await?;
+
+        let current = sqlx::query_as!(MaxVersion, r#"select max(version) as max_version from schema_versions where subject_id = $1;"#, subject_record.id)
+            .fetch_one(&mut *tx)
+            .await?;
 
-        let schema_id = self.insert_schema(&fingerprint, &schema).await?;
-        let _ = self.insert_schema_version(max_version, subject_id, schema_id).await?;
+        if current.max_version.unwrap_or(0) != max_version {
+            tx.rollback().await?;
+            return Ok(None)
+        }
+
+        let schema_id = Self::insert_schema(&mut tx, None, fingerprints, schema, schema_type).await?;
+        Self::insert_schema_references(&mut tx, schema_id, references).await?;
+        Self::insert_schema_version(&mut tx, max_version + 1, subject_record.id, schema_id).await?;
 
         tx.commit().await?;
 
-        Ok(schema_id)
+        Ok(Some(schema_id))
     }
 
-    async fn insert_schema(&self, fingerprint: &String, schema: &String) -> Result<i64, Error> {
-        let res = sqlx::query!(r#"INSERT INTO schemas (fingerprint, json, created_at) VALUES ($1, $2, now()) returning id;"#, fingerprint, schema)
-            .fetch_one(&self.pool)
+    async fn schema_import(&self, subject: &str, version: i32, id: i64, fingerprints: &Fingerprints, payload: &SchemaPayload) -> Result<SchemaImport, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let stored = sqlx::query!(r#"select fingerprint from schemas where id = $1"#, id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let version_schema = sqlx::query!(r#"select sv.schema_id from schema_versions sv inner join subjects sub on sv.subject_id = sub.id where sub.name = $1 and sv.version = $2"#, subject, version)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let existing = sqlx::query!(r#"select id from schemas where fingerprint = $1"#, fingerprints.fingerprint)
+            .fetch_optional(&mut *tx)
             .await?;
 
-        Ok(res.id)
+        // a version keeps its schema, importing it again changes nothing
+        let found = match (stored, version_schema, existing) {
+            (Some(record), _, _) if record.fingerprint != fingerprints.fingerprint => Some(SchemaImport::IdTaken),
+            (_, Some(record), _) if record.schema_id != id => Some(SchemaImport::VersionTaken),
+            (_, Some(_), _) => Some(SchemaImport::Imported),
+            (_, _, Some(record)) if record.id != id => Some(SchemaImport::OtherId(record.id)),
+            _ => None
+        };
+
+        if let Some(found) = found {
+            tx.rollback().await?;
+            return Ok(found)
+        }
+
+        let subject_record = Self::subject_upsert(&mut tx, subject).await?;
+
+        Self::insert_schema(&mut tx, Some(id), fingerprints, &payload.schema, &payload.schema_type).await?;
+        Self::insert_schema_references(&mut tx, id, &payload.references).await?;
+        Self::insert_schema_version(&mut tx, version, subject_record.id, id).await?;
+
+        tx.commit().await?;
+
+        Ok(SchemaImport::Imported)
+    }
+
+    async fn schema_fingerprints(&self) -> Result<Vec<StoredFingerprints>, Error> {
+        sqlx::query_as!(StoredFingerprints, r#"select id, fingerprint, lookup_fingerprint from schemas order by id;"#)
+            .fetch_all(&self.pool)
+            .await
     }
 
-    async fn insert_schema_version(&self, max_version: i32, subject_id: i64, schema_id: i64) -> Result<(), Error> {
-        let _ = sqlx::query!(r#"INSERT INTO schema_versions (version, subject_id, schema_id) VALUES ($1, $2, $3)"#, max_version + 1, subject_id, schema_id).execute(&self.pool).await?;
+    async fn schema_fingerprints_update(&self, id: i64, fingerprints: &Fingerprints) -> Result<(), Error> {
+        let _ = sqlx::query!(r#"UPDATE schemas SET fingerprint = CASE WHEN EXISTS (SELECT 1 FROM schemas WHERE fingerprint = $2 AND id <> $1) THEN fingerprint ELSE $2 END, lookup_fingerprint = $3 WHERE id = $1"#, id, fingerprints.fingerprint, fingerprints.lookup_fingerprint)
+            .execute(&self.pool)
+            .await?;
 
         Ok(())
     }
 
-    async fn subject_versions(&self, subject: &String) -> Result<Vec<i32>, Error> {
-        let res = sqlx::query!(r#"SELECT version FROM subjects s INNER JOIN schema_versions sv ON s.id = sv.subject_id WHERE s.deleted_at is null and s.name = $1;"#, subject)
+    async fn schema_references(&self, schema_id: i64) -> Result<Vec<SchemaReference>, Error> {
+        sqlx::query_as!(SchemaReference, r#"select name, subject, version from schema_references where schema_id = $1 order by id;"#, schema_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn schema_subjects(&self, schema_id: i64, deleted: bool) -> Result<Vec<String>, Error> {
+        let res = sqlx::query!(r#"select distinct sub.name as name from schema_versions sv inner join subjects sub on sv.subject_id = sub.id where ($2 or (sub.deleted_at is null and sv.deleted_at is null)) and sv.schema_id = $1 order by sub.name;"#, schema_id, deleted)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.into_iter().map(|x| x.name).collect())
+    }
+
+    async fn schema_versions(&self, schema_id: i64, deleted: bool) -> Result<Vec<SubjectVersion>, Error> {
+        sqlx::query_as!(SubjectVersion, r#"select sub.name as subject, sv.version as version from schema_versions sv inner join subjects sub on sv.subject_id = sub.id where ($2 or (sub.deleted_at is null and sv.deleted_at is null)) and sv.schema_id = $1 order by sub.name, sv.version;"#, schema_id, deleted)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn schema_all(&self, subject_prefix: &str, latest_only: bool, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<SubjectSchema>, Error> {
+        sqlx::query_as!(SubjectSchema, r#"select sub.name as subject, sv.version as version, sch.id as id, sch.json as schema, sch.schema_type as schema_type from schemas sch inner join schema_versions sv on sch.id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id where ($3 or (sub.deleted_at is null and sch.deleted_at is null and sv.deleted_at is null)) and starts_with(sub.name, $1) and (not $2 or sv.version = (select max(latest.version) from schema_versions latest where latest.subject_id = sv.subject_id and ($3 or latest.deleted_at is null))) order by sub.name, sv.version offset $4 limit $5;"#, subject_prefix, latest_only, deleted, offset, limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn schema_referenced_by(&self, subject: &str, version: i32) -> Result<Vec<i64>, Error> {
+        let res = sqlx::query!(r#"select distinct sr.schema_id as schema_id from schema_references sr inner join schema_versions sv on sr.schema_id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id inner join schemas sch on sch.id = sr.schema_id where sub.deleted_at is null and sch.deleted_at is null and sv.deleted_at is null and sr.subject = $1 and sr.version = $2 order by sr.schema_id;"#, subject, version)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.iter().map(|x| x.schema_id).collect())
+    }
+
+    async fn subject_versions(&self, subject: &str, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<i32>, Error> {
+        let res = sqlx::query!(r#"SELECT version FROM subjects s INNER JOIN schema_versions sv ON s.id = sv.subject_id WHERE ($2 or (s.deleted_at is null and sv.deleted_at is null)) and s.name = $1 ORDER BY version OFFSET $3 LIMIT $4;"#, subject, deleted, offset, limit)
             .fetch_all(&self.pool)
             .await?;
 
@@ -125,37 +418,77 @@ impl Repository for PgRepository {
         Ok(res.iter().map(|x| x.version).collect())
     }
 
-    async fn subject_find(&self, subject: &String) -> Result<Option<Subject>, Error> {
+    async fn subject_find(&self, subject: &str) -> Result<Option<Subject>, Error> {
         sqlx::query_as!(Subject, r#"SELECT id, name FROM subjects WHERE deleted_at is null and name = $1"#, subject).fetch_optional(&self.pool).await
     }
 
-    async fn subject_all(&self) -> Result<Vec<Subject>, Error> {
-        sqlx::query_as!(Subject, r#"SELECT id, name FROM subjects WHERE deleted_at is null"#).fetch_all(&self.pool).await
+    async fn subject_find_deleted(&self, subject: &str) -> Result<Option<Subject>, Error> {
+        sqlx::query_as!(Subject, r#"SELECT id, name FROM subjects WHERE name = $1"#, subject).fetch_optional(&self.pool).await
     }
 
-    async fn subject_schemas(&self, subject: &String) -> Result<Vec<VersionedSchema>, Error> {
-        sqlx::query_as!(VersionedSchema, r#"select sv.version as version, sch.id as id, sch.json as schema from schemas sch inner join schema_versions sv on sch.id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id where sub.deleted_at is null and sch.deleted_at is null and sub.name = $1 order by sv.version desc;"#, subject)
+    async fn subject_reserve(&self, subject: &str) -> Result<Subject, Error> {
+        sqlx::query_as!(Subject, r#"INSERT INTO subjects (name, created_at, deleted_at) VALUES ($1, now(), now()) ON CONFLICT (name) DO UPDATE SET name = excluded.name RETURNING id, name"#, subject)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    async fn subject_all(&self, subject_prefix: &str, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<Subject>, Error> {
+        sqlx::query_as!(Subject, r#"SELECT id, name FROM subjects WHERE ($2 or deleted_at is null) and starts_with(name, $1) ORDER BY name OFFSET $3 LIMIT $4"#, subject_prefix, deleted, offset, limit).fetch_all(&self.pool).await
+    }
+
+    async fn subject_schemas(&self, subject: &str) -> Result<Vec<VersionedSchema>, Error> {
+        sqlx::query_as!(VersionedSchema, r#"select sv.version as version, sch.id as id, sch.json as schema, sch.schema_type as schema_type from schemas sch inner join schema_versions sv on sch.id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id where sub.deleted_at is null and sch.deleted_at is null and sv.deleted_at is null and sub.name = $1 order by sv.version desc;"#, subject)
             .fetch_all(&self.pool)
             .await
     }
 
-    async fn config_get_subject(&self, subject_id: Option<i64>) -> Result<Option<SchemaCompatibility>, Error> {
-        sqlx::query_as!(SchemaCompatibility, r#"select compatibility from configs where subject_id = $1"#, subject_id)
+    async fn config_get_subject(&self, subject_id: Option<i64>) -> Result<Option<Config>, Error> {
+        let res = sqlx::query_as!(ConfigRecord, r#"select compatibility, normalize, alias, compatibility_group, default_metadata, override_metadata, default_rule_set, override_rule_set from configs where subject_id is not distinct from $1"#, subject_id)
             .fetch_optional(&self.pool)
-            .await
+            .await?;
+
+        Ok(res.map(Config::from))
     }
 
-    async fn config_set_subject(&self, subject_id: Option<i64>, compatibility: &Compatibility) -> Result<(), Error> {
-        let _ = sqlx::query!(r#"insert into configs (compatibility, created_at, updated_at, subject_id) values ($1, now(), now(), $2) on conflict (subject_id) do update set updated_at = now(), compatibility = excluded.compatibility"#, Some(compatibility.as_str()), subject_id)
+    async fn config_set_subject(&self, subject_id: Option<i64>, config: &Config) -> Result<(), Error> {
+        let record = ConfigRecord::from(config);
+        // the index on configs treats nulls as equal, so the global config is a single row too
+        let _ = sqlx::query!(r#"insert into configs (compatibility, normalize, alias, compatibility_group, default_metadata, override_metadata, default_rule_set, override_rule_set, created_at, updated_at, subject_id) values ($1, $2, $3, $4, $5, $6, $7, $8, now(), now(), $9) on conflict ((coalesce(subject_id, 0))) do update set updated_at = now(), compatibility = excluded.compatibility, normalize = excluded.normalize, alias = excluded.alias, compatibility_group = excluded.compatibility_group, default_metadata = excluded.default_metadata, override_metadata = excluded.override_metadata, default_rule_set = excluded.default_rule_set, override_rule_set = excluded.override_rule_set"#,
+                record.compatibility, record.normalize, record.alias, record.compatibility_group, record.default_metadata, record.override_metadata, record.default_rule_set, record.override_rule_set, subject_id)
             .execute(&self.pool)
             .await?;
 
         Ok(())
     }
     
-    async fn max_version(&self, subject: &String) -> Result<Option<MaxVersion>, Error> {
-        sqlx::query_as!(MaxVersion, r#"select max(version) as max_version from schema_versions sv inner join subjects sub on sv.subject_id = sub.id where sub.name = $1;"#, subject)
+    async fn max_version(&self, subject: &str, deleted: bool) -> Result<Option<MaxVersion>, Error> {
+        sqlx::query_as!(MaxVersion, r#"select max(version) as max_version from schema_versions sv inner join subjects sub on sv.subject_id = sub.id where ($2 or sv.deleted_at is null) and sub.name = $1;"#, subject, deleted)
             .fetch_optional(&self.pool)
             .await
     }
+
+    async fn config_delete_subject(&self, subject_id: Option<i64>) -> Result<Option<Config>, Error> {
+        let res = sqlx::query_as!(ConfigRecord, r#"delete from configs where subject_id is not distinct from $1 returning compatibility, normalize, alias, compatibility_group, default_metadata, override_metadata, default_rule_set, override_rule_set"#, subject_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(Config::from))
+    }
+
+    async fn mode_get_subject(&self, subject_id: Option<i64>) -> Result<Option<Mode>, Error> {
+        let res = sqlx::query_as!(SchemaMode, r#"select mode from modes where subject_id is not distinct from $1"#, subject_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(|x| x.mode))
+    }
+
+    async fn mode_set_subject(&self, subject_id: Option<i64>, mode: &Mode) -> Result<(), Error> {
+        // the index on modes treats nulls as equal, so the global mode is a single row too
+        let _ = sqlx::query!(r#"insert into modes (mode, created_at, updated_at, subject_id) values ($1, now(), now(), $2) on conflict ((coalesce(subject_id, 0))) do update set updated_at = now(), mode = excluded.mode"#, mode.as_str(), subject_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
 }
\ No newline at end of file
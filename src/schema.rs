@@ -0,0 +1,211 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use crate::avro::AvroSchemaProvider;
+use crate::data::{Fingerprints, SchemaType};
+use crate::json_schema::JsonSchemaProvider;
+use crate::protobuf::ProtobufSchemaProvider;
+
+#[derive(Debug)]
+pub struct InvalidSchema(pub String);
+
+// a referenced schema, with the references of its own already resolved
+pub struct ResolvedReference {
+    pub name: String,
+    pub subject: String,
+    pub version: i32,
+    pub schema: String
+}
+
+// one reason why a reader cannot read what a writer wrote, `path` points into the reader's schema
+#[derive(Debug, Clone)]
+pub struct Incompatibility {
+    pub path: String,
+    pub reason: String,
+    pub reader_type: String,
+    pub writer_type: String
+}
+
+impl Incompatibility {
+    pub fn new(path: &str, reason: &str, reader_type: &str, writer_type: &str) -> Incompatibility {
+        Incompatibility { path: path.to_string(), reason: reason.to_string(), reader_type: reader_type.to_string(), writer_type: writer_type.to_string() }
+    }
+}
+
+// a schema format the registry can store, selected by the `schemaType` of a schema
+pub trait SchemaProvider: Send + Sync + 'static {
+    type Schema: Send + Sync + 'static;
+
+    fn schema_type(&self) -> &'static str;
+
+    fn parse(&self, schema: &str, references: &[ResolvedReference]) -> Result<Self::Schema, InvalidSchema>;
+
+    // what lookups with ?normalize=true compare, may leave out what doesn't change how data is read
+    fn canonical_form(&self, schema: &Self::Schema) -> String;
+
+    // a deterministic text of everything the schema says, whatever its layout, which tells stored schemas apart
+    fn normalized_form(&self, schema: &Self::Schema) -> String {
+        self.canonical_form(schema)
+    }
+
+    // what registrations with ?normalize=true store, None when the normalized form isn't a schema of this type
+    fn normalized_schema(&self, schema: &Self::Schema) -> Option<String> {
+        Some(self.normalized_form(schema))
+    }
+
+    fn incompatibilities(&self, writers_schema: &Self::Schema, readers_schema: &Self::Schema) -> Vec<Incompatibility>;
+
+    // the names of the other schemas the schema imports, each of which has to be one of its references
+    fn references(&self, _schema: &Self::Schema) -> Vec<String> {
+        vec![]
+    }
+}
+
+trait DynSchemaProvider: Send + Sync {
+    fn parse(&self, schema: &str, references: &[ResolvedReference]) -> Result<Box<dyn Any + Send + Sync>, InvalidSchema>;
+    fn canonical_form(&self, schema: &dyn Any) -> String;
+    fn normalized_form(&self, schema: &dyn Any) -> String;
+    fn normalized_schema(&self, schema: &dyn Any) -> Option<String>;
+    fn incompatibilities(&self, writers_schema: &dyn Any, readers_schema: &dyn Any) -> Vec<Incompatibility>;
+    fn references(&self, schema: &dyn Any) -> Vec<String>;
+}
+
+impl <P : SchemaProvider> DynSchemaProvider for P {
+    fn parse(&self, schema: &str, references: &[ResolvedReference]) -> Result<Box<dyn Any + Send + Sync>, InvalidSchema> {
+        let res = SchemaProvider::parse(self, schema, references)?;
+        Ok(Box::new(res))
+    }
+
+    fn canonical_form(&self, schema: &dyn Any) -> String {
+        schema.downcast_ref().map(|x| SchemaProvider::canonical_form(self, x)).unwrap_or_default()
+    }
+
+    fn normalized_form(&self, schema: &dyn Any) -> String {
+        schema.downcast_ref().map(|x| SchemaProvider::normalized_form(self, x)).unwrap_or_default()
+    }
+
+    fn normalized_schema(&self, schema: &dyn Any) -> Option<String> {
+        schema.downcast_ref().and_then(|x| SchemaProvider::normalized_schema(self, x))
+    }
+
+    fn incompatibilities(&self, writers_schema: &dyn Any, readers_schema: &dyn Any) -> Vec<Incompatibility> {
+        match (writers_schema.downcast_ref(), readers_schema.downcast_ref()) {
+            (Some(writer), Some(reader)) => SchemaProvider::incompatibilities(self, writer, reader),
+            _ => vec![Incompatibility::new("/", "the schemas cannot be compared", "", "")]
+        }
+    }
+
+    fn references(&self, schema: &dyn Any) -> Vec<String> {
+        schema.downcast_ref().map(|x| SchemaProvider::references(self, x)).unwrap_or_default()
+    }
+}
+
+pub struct ParsedSchema {
+    schema_type: SchemaType,
+    provider: Arc<dyn DynSchemaProvider>,
+    schema: Box<dyn Any + Send + Sync>,
+    reference_keys: Vec<String>
+}
+
+impl ParsedSchema {
+    pub fn canonical_form(&self) -> String {
+        self.provider.canonical_form(self.schema.as_ref())
+    }
+
+    pub fn normalized_form(&self) -> String {
+        self.provider.normalized_form(self.schema.as_ref())
+    }
+
+    pub fn normalized_schema(&self) -> Option<String> {
+        self.provider.normalized_schema(self.schema.as_ref())
+    }
+
+    // the fingerprint schemas are stored under, a doc-only change is another schema
+    pub fn fingerprint(&self) -> String {
+        self.digest(&self.normalized_form())
+    }
+
+    // the fingerprint lookups with ?normalize=true compare, see SchemaProvider::canonical_form
+    pub fn lookup_fingerprint(&self) -> String {
+        self.digest(&self.canonical_form())
+    }
+
+    pub fn fingerprints(&self) -> Fingerprints {
+        Fingerprints { fingerprint: self.fingerprint(), lookup_fingerprint: self.lookup_fingerprint() }
+    }
+
+    // the same text with other references is another schema, so the references are part of the fingerprint
+    fn digest(&self, form: &str) -> String {
+        let fingerprint = format!("{:x}", Sha256::digest(form));
+
+        if self.reference_keys.is_empty() {
+            return fingerprint
+        }
+
+        let mut digest = Sha256::new();
+        digest.update(fingerprint);
+        for key in &self.reference_keys {
+            digest.update(key);
+        }
+        format!("{:x}", digest.finalize())
+    }
+
+    // schemas of different types never read each other
+    pub fn incompatibilities(writers_schema: &ParsedSchema, readers_schema: &ParsedSchema) -> Vec<Incompatibility> {
+        if writers_schema.schema_type != readers_schema.schema_type {
+            return vec![Incompatibility::new("/", "the schema types differ", readers_schema.schema_type.as_str(), writers_schema.schema_type.as_str())]
+        }
+
+        writers_schema.provider.incompatibilities(writers_schema.schema.as_ref(), readers_schema.schema.as_ref())
+    }
+}
+
+#[derive(Clone)]
+pub struct SchemaProviders {
+    providers: HashMap<String, Arc<dyn DynSchemaProvider>>
+}
+
+impl SchemaProviders {
+    pub fn empty() -> SchemaProviders {
+        SchemaProviders { providers: HashMap::new() }
+    }
+
+    pub fn register<P : SchemaProvider>(&mut self, provider: P) {
+        self.providers.insert(provider.schema_type().to_string(), Arc::new(provider));
+    }
+
+    pub fn schema_types(&self) -> Vec<String> {
+        let mut res: Vec<String> = self.providers.keys().cloned().collect();
+        res.sort();
+        res
+    }
+
+    pub fn parse(&self, schema_type: &SchemaType, schema: &str, references: &[ResolvedReference]) -> Result<ParsedSchema, InvalidSchema> {
+        let provider = self.providers
+            .get(schema_type.as_str())
+            .ok_or(InvalidSchema(format!("schema type {} is not supported", schema_type.as_str())))?;
+
+        // resolved references list nested ones too, in the order they were found, so they are sorted to not depend on it
+        let mut reference_keys: Vec<String> = references.iter().map(|x| format!("{}|{}|{};", x.name, x.subject, x.version)).collect();
+        reference_keys.sort();
+
+        let parsed = provider.parse(schema, references)?;
+        if let Some(name) = provider.references(parsed.as_ref()).into_iter().find(|name| !references.iter().any(|x| &x.name == name)) {
+            return Err(InvalidSchema(format!("{} does not match any reference", name)))
+        }
+
+        Ok(ParsedSchema { schema_type: schema_type.clone(), provider: provider.clone(), schema: parsed, reference_keys })
+    }
+}
+
+impl Default for SchemaProviders {
+    fn default() -> Self {
+        let mut providers = SchemaProviders::empty();
+        providers.register(AvroSchemaProvider);
+        providers.register(ProtobufSchemaProvider);
+        providers.register(JsonSchemaProvider);
+        providers
+    }
+}
@@ -0,0 +1,215 @@
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::data::*;
+use crate::error::AppError;
+use crate::repository::Repository;
+use crate::service::Service;
+
+// the records Confluent's registry keeps in its _schemas topic, dumped as one {"key": ..., "value": ...} object per line
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "keytype", rename_all = "SCREAMING_SNAKE_CASE")]
+enum RecordKey {
+    Schema { subject: String, version: i32, #[serde(default)] magic: i32 },
+    Config { subject: Option<String>, #[serde(default)] magic: i32 },
+    DeleteSubject { subject: String, #[serde(default)] magic: i32 },
+    Mode { subject: Option<String>, #[serde(default)] magic: i32 },
+    // NOOP and the keys of newer registries
+    #[serde(other)]
+    Other
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SchemaValue {
+    subject: String,
+    version: i32,
+    id: i64,
+    #[serde(default, skip_serializing_if = "SchemaType::is_avro")]
+    schema_type: SchemaType,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    references: Vec<SchemaReference>,
+    schema: String,
+    #[serde(default)]
+    deleted: bool
+}
+
+#[derive(Serialize)]
+struct DeleteSubjectValue {
+    subject: String,
+    // the latest version when the subject was deleted
+    version: i32
+}
+
+#[derive(Serialize, Deserialize)]
+struct ModeValue {
+    mode: Mode
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record<V> {
+    key: RecordKey,
+    // null for tombstones
+    value: Option<V>
+}
+
+#[derive(Serialize, Default)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub skipped: usize
+}
+
+impl ImportSummary {
+    fn count(&mut self, imported: bool) {
+        match imported {
+            true => self.imported += 1,
+            false => self.skipped += 1
+        }
+    }
+}
+
+fn invalid_record(error: serde_json::Error) -> AppError {
+    AppError::InvalidRequest(StatusCode::UNPROCESSABLE_ENTITY, error.to_string())
+}
+
+// a record as it is imported, read in full before any record is written
+enum ImportRecord {
+    Schema(SchemaValue),
+    // Confluent's registry writes a tombstone for a version it deleted permanently
+    Tombstone(String, i32),
+    Config(Option<String>, Box<Config>),
+    Mode(Option<String>, Mode),
+    DeleteSubject(String),
+    Other
+}
+
+impl ImportRecord {
+    fn parse(line: &str) -> Result<ImportRecord, AppError> {
+        let record: Record<Value> = serde_json::from_str(line).map_err(invalid_record)?;
+
+        let res = match (record.key, record.value) {
+            (RecordKey::Schema { .. }, Some(value)) => ImportRecord::Schema(serde_json::from_value(value).map_err(invalid_record)?),
+            (RecordKey::Schema { subject, version, .. }, None) => ImportRecord::Tombstone(subject, version),
+            (RecordKey::Config { subject, .. }, Some(value)) => ImportRecord::Config(subject, serde_json::from_value(value).map_err(invalid_record)?),
+            (RecordKey::Mode { subject, .. }, Some(value)) => ImportRecord::Mode(subject, serde_json::from_value::<ModeValue>(value).map_err(invalid_record)?.mode),
+            (RecordKey::DeleteSubject { subject, .. }, Some(_)) => ImportRecord::DeleteSubject(subject),
+            _ => ImportRecord::Other
+        };
+
+        Ok(res)
+    }
+}
+
+// replays the records in order, keeping the ids and versions they were registered with, once every line has been read
+// configs and modes are set last, once their subjects exist
+// records already imported are left as they are, so an import that failed part way can be run again as a whole
+pub async fn import<R : Repository + Send + Sync>(svc: &Service<R>, records: &str) -> Result<ImportSummary, AppError> {
+    let mut summary = ImportSummary::default();
+    let mut others = vec![];
+    let mut settings = vec![];
+
+    for (index, line) in records.lines().enumerate().filter(|(_, x)| !x.trim().is_empty()) {
+        match ImportRecord::parse(line).map_err(|e| AppError::ImportFailed(index + 1, Box::new(e)))? {
+            record @ (ImportRecord::Config(..) | ImportRecord::Mode(..)) => settings.push((index, record)),
+            record => others.push((index, record))
+        }
+    }
+
+    for (index, record) in others.into_iter().chain(settings) {
+        let imported = import_record(svc, record).await.map_err(|e| AppError::ImportFailed(index + 1, Box::new(e)))?;
+        summary.count(imported);
+    }
+
+    Ok(summary)
+}
+
+// false for the records without a counterpart in this registry
+async fn import_record<R : Repository + Send + Sync>(svc: &Service<R>, record: ImportRecord) -> Result<bool, AppError> {
+    match record {
+        ImportRecord::Schema(value) => {
+            let payload = SchemaPayload { schema: value.schema, schema_type: value.schema_type, references: value.references, id: None, version: None };
+
+            svc.schema_import(&value.subject, value.version, value.id, &payload).await?;
+            if value.deleted {
+                svc.schema_import_delete(&value.subject, value.version).await?;
+            }
+
+            Ok(true)
+        },
+        ImportRecord::Tombstone(subject, version) => {
+            svc.schema_import_purge(&subject, version).await?;
+            Ok(true)
+        },
+        ImportRecord::Config(subject, value) => {
+            match *value != Config::default() {
+                true => svc.config_import(subject.as_deref(), &value).await,
+                false => Ok(false)
+            }
+        },
+        ImportRecord::Mode(subject, mode) => svc.mode_import(subject.as_deref(), &mode).await,
+        ImportRecord::DeleteSubject(subject) => {
+            svc.subject_import_delete(&subject).await?;
+            Ok(true)
+        },
+        ImportRecord::Other => Ok(false)
+    }
+}
+
+fn record<T : Serialize>(key: RecordKey, value: T) -> Result<String, AppError> {
+    serde_json::to_string(&Record { key, value: Some(value) }).map_err(invalid_record)
+}
+
+// the versions in the order of their ids, so referenced schemas come before the ones using them, soft deleted ones marked
+// as deleted, then the deleted subjects, the configs and the modes
+pub async fn export<R : Repository + Send + Sync>(svc: &Service<R>) -> Result<String, AppError> {
+    let mut schemas = vec![];
+    let mut deletes = vec![];
+    let mut configs = vec![];
+    let mut modes = vec![];
+
+    if let Some(config) = svc.config_get_subject(None).await? {
+        configs.push(record(RecordKey::Config { subject: None, magic: 0 }, config)?);
+    }
+    if let Some(mode) = svc.mode_get_subject(None).await? {
+        modes.push(record(RecordKey::Mode { subject: None, magic: 0 }, ModeValue { mode })?);
+    }
+
+    for subject in svc.subject_all(&SubjectsParams { deleted: true, ..SubjectsParams::default() }).await? {
+        for schema in svc.subject_schemas(&subject.name).await? {
+            let references = svc.schema_find_by_id(schema.id).await?.map(|x| x.references).unwrap_or_default();
+            schemas.push(SchemaValue { subject: subject.name.clone(), version: schema.version, id: schema.id, schema_type: schema.schema_type, references, schema: schema.schema, deleted: false });
+        }
+        for schema in svc.subject_deleted_schemas(&subject.name).await? {
+            let references = svc.schema_find_by_id(schema.id).await?.map(|x| x.references).unwrap_or_default();
+            schemas.push(SchemaValue { subject: subject.name.clone(), version: schema.version, id: schema.id, schema_type: schema.schema_type, references, schema: schema.schema, deleted: true });
+        }
+
+        // subjects reserved for an alias have no versions to delete
+        if svc.subject_named(&subject.name).await?.is_none() {
+            if let Some(version) = schemas.iter().filter(|x| x.subject == subject.name).map(|x| x.version).max() {
+                deletes.push(record(RecordKey::DeleteSubject { subject: subject.name.clone(), magic: 0 }, DeleteSubjectValue { subject: subject.name.clone(), version })?);
+            }
+        }
+
+        if let Some(config) = svc.config_get_subject(Some(&subject.name)).await? {
+            configs.push(record(RecordKey::Config { subject: Some(subject.name.clone()), magic: 0 }, config)?);
+        }
+        if let Some(mode) = svc.mode_get_subject(Some(&subject.name)).await? {
+            modes.push(record(RecordKey::Mode { subject: Some(subject.name.clone()), magic: 0 }, ModeValue { mode })?);
+        }
+    }
+
+    schemas.sort_by(|a, b| (a.id, &a.subject, a.version).cmp(&(b.id, &b.subject, b.version)));
+
+    let mut res = vec![];
+    for schema in schemas {
+        res.push(record(RecordKey::Schema { subject: schema.subject.clone(), version: schema.version, magic: 1 }, schema)?);
+    }
+    res.extend(deletes);
+    res.extend(configs);
+    res.extend(modes);
+
+    Ok(res.iter().map(|x| format!("{}\n", x)).collect())
+}
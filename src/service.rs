@@ -1,13 +1,20 @@
-use apache_avro::{Schema as AvroSchema, schema_compatibility::SchemaCompatibility as AvroSchemaCompatibility};
 use async_recursion::async_recursion;
-use sha2::Sha256;
 use crate::error::AppError;
 use crate::data::*;
 use crate::repository::*;
+use crate::schema::{Incompatibility, InvalidSchema, ParsedSchema, ResolvedReference, SchemaProviders};
+
+const INSERT_ATTEMPTS: usize = 3;
+
+// the offset and limit of a listing, where a negative or zero limit lists everything
+fn page(offset: i64, limit: i64) -> (i64, Option<i64>) {
+    (offset.max(0), (limit > 0).then_some(limit))
+}
 
 #[derive(Clone)]
 pub struct Service<R> {
-    pub repository: R
+    pub repository: R,
+    pub providers: SchemaProviders
 }
 
 impl <R : Repository + Send + Sync> Service<R> {
@@ -16,20 +23,71 @@ impl <R : Repository + Send + Sync> Service<R> {
         Ok(res)
     }
 
-    pub async fn schema_find_by_version(&self, subject: &String, version_id: &VersionId) -> Result<Option<FindBySchemaResponse>, AppError> {
-        let version = self.version_id(&subject, &version_id).await?.ok_or(AppError::SchemaNotFound(subject.clone(), version_id.clone()))?;
-        let res = self.repository.schema_find_by_version(&subject, version).await?;
+    pub async fn schema_subjects(&self, id: i64, deleted: bool) -> Result<Vec<String>, AppError> {
+        self.schema_required(id).await?;
+        let res = self.repository.schema_subjects(id, deleted).await?;
+        Ok(res)
+    }
 
+    pub async fn schema_versions(&self, id: i64, deleted: bool) -> Result<Vec<SubjectVersion>, AppError> {
+        self.schema_required(id).await?;
+        let res = self.repository.schema_versions(id, deleted).await?;
         Ok(res)
     }
 
-    pub async fn schema_delete_by_version(&self, subject: &String, version_id: &VersionId) -> Result<u64, AppError> {
-        let version = self.version_id(&subject, &version_id).await?.ok_or(AppError::SchemaNotFound(subject.clone(), version_id.clone()))?;
-        let res = self.repository.schema_find_by_version(&subject, version).await?;
+    pub async fn schema_all(&self, params: &SchemasParams) -> Result<Vec<FindBySchemaResponse>, AppError> {
+        let (offset, limit) = page(params.offset, params.limit);
+        let schemas = self.repository.schema_all(&params.subject_prefix, params.latest_only, params.deleted, offset, limit).await?;
+
+        let mut res = vec![];
+        for schema in schemas {
+            res.push(self.find_response(schema).await?);
+        }
+
+        Ok(res)
+    }
+
+    pub fn schema_types(&self) -> Vec<String> {
+        self.providers.schema_types()
+    }
+
+    async fn schema_required(&self, id: i64) -> Result<SchemaPayload, AppError> {
+        self.schema_find_by_id(id).await?.ok_or(AppError::SchemaIdNotFound(id))
+    }
+
+    pub async fn schema_find_by_version(&self, subject: &str, version_id: &VersionId, deleted: bool) -> Result<Option<FindBySchemaResponse>, AppError> {
+        let subject = &self.subject_alias(subject).await?;
+        let version = self.version_id(subject, version_id, deleted).await?.ok_or(AppError::VersionNotFound(subject.to_string(), version_id.clone()))?;
+        match self.repository.schema_find_by_version(subject, version, deleted).await? {
+            Some(res) => Ok(Some(self.find_response(res).await?)),
+            None => Ok(None)
+        }
+    }
+
+    async fn find_response(&self, res: SubjectSchema) -> Result<FindBySchemaResponse, AppError> {
+        let references = self.repository.schema_references(res.id).await?;
+
+        Ok(FindBySchemaResponse { subject: res.subject, version: res.version, id: res.id, schema_type: res.schema_type, references, schema: res.schema })
+    }
+
+    pub async fn schema_delete_by_version(&self, subject: &str, version_id: &VersionId, permanent: bool) -> Result<u64, AppError> {
+        self.unaliased(subject).await?;
+        self.writable(subject).await?;
+        let version = self.version_id(subject, version_id, false).await?.ok_or(AppError::VersionNotFound(subject.to_string(), version_id.clone()))?;
+
+        if permanent {
+            return self.schema_permanent_delete(subject, version_id, version).await
+        }
+
+        let res = self.repository.schema_find_by_version(subject, version, false).await?;
 
         let affected: Result<u64, AppError> = match res {
             Some(resp) => {
-                let affected = self.repository.schema_soft_delete(resp.id).await?;
+                if !self.repository.schema_referenced_by(subject, version).await?.is_empty() {
+                    return Err(AppError::ReferenceExists(subject.to_string(), version))
+                }
+
+                let affected = self.repository.schema_soft_delete(subject, resp.id).await?;
                 Ok(affected)
             },
             None => Ok(0)
@@ -38,124 +96,501 @@ impl <R : Repository + Send + Sync> Service<R> {
         affected
     }
 
-    pub async fn delete_subject(&self, subject: &String) -> Result<Vec<i64>, AppError> {
-        let resp = self.repository.subject_soft_delete(&subject).await?;
+    // like in Confluent's registry, only versions that were soft deleted can be deleted permanently
+    async fn schema_permanent_delete(&self, subject: &str, version_id: &VersionId, version: i32) -> Result<u64, AppError> {
+        match self.repository.schema_version_deleted(subject, version).await? {
+            Some(true) => {
+                if !self.repository.schema_referenced_by(subject, version).await?.is_empty() {
+                    return Err(AppError::ReferenceExists(subject.to_string(), version))
+                }
+
+                let affected = self.repository.schema_permanent_delete(subject, version).await?;
+                Ok(affected)
+            },
+            Some(false) => Err(AppError::VersionNotSoftDeleted(subject.to_string(), version)),
+            None => Err(AppError::VersionNotFound(subject.to_string(), version_id.clone()))
+        }
+    }
+
+    pub async fn delete_subject(&self, subject: &str, permanent: bool) -> Result<Vec<i64>, AppError> {
+        self.unaliased(subject).await?;
+
+        if permanent {
+            return self.subject_permanent_delete(subject).await
+        }
+
+        self.subject_known(subject, false).await?;
+        self.writable(subject).await?;
+
+        for version in self.repository.subject_versions(subject, false, 0, None).await? {
+            if !self.repository.schema_referenced_by(subject, version).await?.is_empty() {
+                return Err(AppError::ReferenceExists(subject.to_string(), version))
+            }
+        }
+
+        let resp = self.repository.subject_soft_delete(subject).await?;
+
+        Ok(resp)
+    }
+
+    async fn subject_permanent_delete(&self, subject: &str) -> Result<Vec<i64>, AppError> {
+        match self.repository.subject_deleted(subject).await? {
+            Some(true) => {
+                self.writable(subject).await?;
+                let resp = self.repository.subject_permanent_delete(subject).await?;
+                Ok(resp)
+            },
+            Some(false) => Err(AppError::SubjectNotSoftDeleted(subject.to_string())),
+            None => Err(AppError::SubjectNotFound(subject.to_string()))
+        }
+    }
+
+    // restores a soft deleted version under the number it had
+    pub async fn schema_undelete(&self, subject: &str, version_id: &VersionId) -> Result<u64, AppError> {
+        self.unaliased(subject).await?;
+        self.writable(subject).await?;
+        let version = self.version_id(subject, version_id, true).await?.ok_or(AppError::VersionNotFound(subject.to_string(), version_id.clone()))?;
+
+        if self.repository.schema_version_deleted(subject, version).await?.is_none() {
+            return Err(AppError::VersionNotFound(subject.to_string(), version_id.clone()))
+        }
+
+        let affected = self.repository.schema_undelete(subject, version).await?;
+        Ok(affected)
+    }
+
+    pub async fn subject_undelete(&self, subject: &str) -> Result<Vec<i64>, AppError> {
+        self.unaliased(subject).await?;
+        self.subject_known(subject, true).await?;
+        self.writable(subject).await?;
 
+        let resp = self.repository.subject_undelete(subject).await?;
         Ok(resp)
     }
 
-    pub async fn schema_find_by_schema(&self, subject: &String, schema: &String) -> Result<Option<FindBySchemaResponse>, AppError> {
-        let avro_schema = AvroSchema::parse_str(schema.as_str())?;
-        let fingerprint = avro_schema.fingerprint::<Sha256>().to_string();
-        let res = self.repository.schema_find_by_schema(&subject, &fingerprint).await?;
+    pub async fn referenced_by(&self, subject: &str, version_id: &VersionId) -> Result<Vec<i64>, AppError> {
+        let subject = &self.subject_alias(subject).await?;
+        let version = self.version_id(subject, version_id, false).await?.ok_or(AppError::VersionNotFound(subject.to_string(), version_id.clone()))?;
+        let res = self.repository.schema_referenced_by(subject, version).await?;
 
         Ok(res)
     }
 
-    pub async fn schema_insert(&self, subject: &String, schema: &String) -> Result<RegisterSchemaResponse, AppError> {
-        let avro_schema = AvroSchema::parse_str(schema.as_str())?;
-        let fingerprint = avro_schema.fingerprint::<Sha256>().to_string();
+    // referenced schemas come before the schemas that use them, each one only once, soft deleted versions resolve too
+    // like in Confluent's registry, so stored schemas still parse and imports may delete a version before its referrers
+    #[async_recursion]
+    async fn resolve_references(&self, references: &[SchemaReference], resolved: &mut Vec<ResolvedReference>) -> Result<(), AppError> {
+        for reference in references {
+            if resolved.iter().any(|x| x.name == reference.name) {
+                continue
+            }
 
-        let subject_record = self.subject_find(&subject).await?.ok_or(AppError::SubjectNotFound(subject.clone()))?;
-        let subject_schemas = self.subject_schemas(&subject).await?;
+            let schema_record = self.repository
+                .schema_find_by_version(&reference.subject, reference.version, true)
+                .await?
+                .ok_or(InvalidSchema(format!("reference {} to {} version {} was not found", reference.name, reference.subject, reference.version)))?;
 
-        let subject_compatibility = self.config_get_subject(Some(subject)).await?.map(|x| x.compatibility);
-        let global_compatibility = self.config_get_subject(None).await?.map(|x| x.compatibility);
+            let nested = self.repository.schema_references(schema_record.id).await?;
+            self.resolve_references(&nested, resolved).await?;
 
-        //TODO: can this or_else be lazy?
-        let compatibility = subject_compatibility.or_else(|| global_compatibility).unwrap_or(Compatibility::Backward);
+            resolved.push(ResolvedReference { name: reference.name.clone(), subject: reference.subject.clone(), version: reference.version, schema: schema_record.schema });
+        }
 
-        let is_compatible = self.schema_compatibility(&subject_schemas, &avro_schema, compatibility).await?;
+        Ok(())
+    }
 
-        if !is_compatible {
-            return Err(AppError::IncompatibleSchema)
+    async fn parse(&self, schema_type: &SchemaType, schema: &str, references: &[SchemaReference]) -> Result<ParsedSchema, AppError> {
+        let mut resolved = vec![];
+        self.resolve_references(references, &mut resolved).await?;
+
+        // the protobuf parser stages the schema and its imports on disk, so parsing runs where blocking is fine
+        let (providers, schema_type, schema) = (self.providers.clone(), schema_type.clone(), schema.to_string());
+        let parsed_schema = tokio::task::spawn_blocking(move || providers.parse(&schema_type, &schema, &resolved))
+            .await
+            .expect("parsing a schema panicked")?;
+
+        Ok(parsed_schema)
+    }
+
+    async fn parse_stored(&self, schema_id: i64, schema_type: &SchemaType, schema: &str) -> Result<ParsedSchema, AppError> {
+        let references = self.repository.schema_references(schema_id).await?;
+        self.parse(schema_type, schema, &references).await
+    }
+
+    // the version of the subject registering the schema, with `normalize` (or the subject's normalize config) a version
+    // whose canonical form matches will do too, e.g. one that only differs in docs
+    pub async fn schema_find_by_schema(&self, subject: &str, payload: &SchemaPayload, normalize: bool) -> Result<Option<FindBySchemaResponse>, AppError> {
+        let subject = &self.subject_alias(subject).await?;
+        let parsed_schema = self.parse(&payload.schema_type, &payload.schema, &payload.references).await?;
+        let normalize = normalize || self.effective_config(subject).await?.normalize.unwrap_or(false);
+        let fingerprint = match normalize {
+            true => parsed_schema.lookup_fingerprint(),
+            false => parsed_schema.fingerprint()
+        };
+
+        match self.repository.schema_find_by_schema(subject, &fingerprint, normalize).await? {
+            Some(res) => Ok(Some(self.find_response(res).await?)),
+            None => Ok(None)
         }
+    }
 
-        let max_version = subject_schemas.first().map(|x| x.version).unwrap_or(0);
-        let schema_id = self.repository.insert(&fingerprint, &schema, subject_record.id, max_version).await?;
+    // schemas stored by an older release can have a fingerprint of another normalized form and no lookup fingerprint,
+    // which lookups would miss, so they are fingerprinted again
+    pub async fn schema_rehash(&self) -> Result<RehashSummary, AppError> {
+        let mut summary = RehashSummary::default();
+
+        for stored in self.repository.schema_fingerprints().await? {
+            let payload = match self.repository.schema_find_by_id(stored.id).await? {
+                Some(payload) => payload,
+                None => continue
+            };
+
+            // a schema that no longer parses, e.g. after a parser upgrade, keeps its fingerprints
+            let parsed_schema = match self.parse(&payload.schema_type, &payload.schema, &payload.references).await {
+                Ok(parsed_schema) => parsed_schema,
+                Err(AppError::InvalidSchema(_)) => {
+                    summary.unparsed.push(stored.id);
+                    continue
+                },
+                Err(e) => return Err(e)
+            };
+
+            let fingerprints = parsed_schema.fingerprints();
+            if stored.fingerprint != fingerprints.fingerprint || stored.lookup_fingerprint.as_ref() != Some(&fingerprints.lookup_fingerprint) {
+                self.repository.schema_fingerprints_update(stored.id, &fingerprints).await?;
+                summary.updated += 1;
+            }
+        }
 
-        Ok(RegisterSchemaResponse{id: schema_id})
+        Ok(summary)
     }
 
-    #[async_recursion]
-    pub async fn schema_compatibility(&self, schemas: &Vec<VersionedSchema>, incoming: &AvroSchema, compatibility: Compatibility) -> Result<bool, AppError> {
-        match compatibility {
-            Compatibility::Backward => {
-                match schemas.first() {
-                    Some(versioned_schema) => {
-                        let db_schema = AvroSchema::parse_str(versioned_schema.schema.as_str())?;
-                        Ok(AvroSchemaCompatibility::can_read(&db_schema, &incoming))
-                    },
-                    None => Ok(true)
-                }
-            },
-            Compatibility::BackwardTransitive => {
-                for s in schemas {
-                    let db_schema = AvroSchema::parse_str(s.schema.as_str())?;
-                    if !AvroSchemaCompatibility::can_read(&db_schema, &incoming) {
-                        return Ok(false)
-                    }
-                }
+    // a schema the subject already has is found by its storage fingerprint whatever the normalize config, so a doc-only
+    // change becomes a new version, unless the registration asks for `normalize`, which compares the lookup fingerprint
+    // and stores the schema in its normalized form
+    pub async fn schema_insert(&self, subject: &str, payload: &SchemaPayload, normalize: bool) -> Result<RegisterSchemaResponse, AppError> {
+        let subject = &self.subject_alias(subject).await?;
+        let parsed_schema = self.parse(&payload.schema_type, &payload.schema, &payload.references).await?;
+        let fingerprints = parsed_schema.fingerprints();
+        let fingerprint = match normalize {
+            true => &fingerprints.lookup_fingerprint,
+            false => &fingerprints.fingerprint
+        };
+
+        let existing = self.repository.schema_find_by_schema(subject, fingerprint, normalize).await?;
+
+        let normalized = parsed_schema.normalized_schema().filter(|_| normalize)
+            .map(|schema| SchemaPayload { schema, schema_type: payload.schema_type.clone(), references: payload.references.clone(), id: payload.id, version: payload.version });
+        let payload = normalized.as_ref().unwrap_or(payload);
+
+        if payload.id.is_some() || payload.version.is_some() {
+            return self.schema_insert_with_id(subject, payload, existing).await
+        }
+
+        if let Some(existing) = existing {
+            return Ok(RegisterSchemaResponse { id: existing.id })
+        }
 
-                return Ok(true)
+        self.writable(subject).await?;
+
+        let compatibility = self.effective_compatibility(subject).await?;
+
+        for _ in 0..INSERT_ATTEMPTS {
+            // soft deleted versions keep their numbers, so they can still be undeleted and purged
+            let max_version = self.repository.max_version(subject, true).await?.and_then(|x| x.max_version).unwrap_or(0);
+
+            // looked up after `max_version`, so a concurrent registration of the same schema is either found here or fails the insert
+            if let Some(existing) = self.repository.schema_find_by_schema(subject, fingerprint, normalize).await? {
+                return Ok(RegisterSchemaResponse { id: existing.id })
+            }
+
+            let subject_schemas = self.subject_schemas(subject).await?;
+            let messages = self.schema_compatibility(&subject_schemas, &parsed_schema, compatibility).await?;
+
+            if !messages.is_empty() {
+                return Err(AppError::IncompatibleSchema(subject.to_string(), messages))
+            }
+
+            // a concurrent registration that took the version first fails the insert as a unique violation
+            match self.repository.insert(subject, &fingerprints, &payload.schema, &payload.schema_type, &payload.references, max_version).await {
+                Ok(Some(schema_id)) => return Ok(RegisterSchemaResponse{id: schema_id}),
+                Ok(None) => (),
+                Err(error) if error.as_database_error().is_some_and(|x| x.is_unique_violation()) => (),
+                Err(error) => return Err(error.into())
+            }
+        }
+
+        Err(AppError::ConcurrentModification)
+    }
+
+    // in IMPORT mode a registration can keep the id and version the schema has in another registry, a schema the subject
+    // already has must keep its id
+    async fn schema_insert_with_id(&self, subject: &str, payload: &SchemaPayload, existing: Option<SubjectSchema>) -> Result<RegisterSchemaResponse, AppError> {
+        if self.effective_mode(subject).await? != Mode::Import {
+            return Err(AppError::OperationNotPermitted(format!("Subject {} is not in import mode", subject)))
+        }
+
+        let id = payload.id.ok_or(AppError::OperationNotPermitted(format!("Subject {} requires a schema id to register a version in import mode", subject)))?;
+
+        if let Some(existing) = existing {
+            return match existing.id == id {
+                true => Ok(RegisterSchemaResponse { id }),
+                false => Err(AppError::IdDoesNotMatch(existing.id, id))
+            }
+        }
+        let version = match payload.version {
+            Some(version) => version,
+            None => self.repository.max_version(subject, true).await?.and_then(|x| x.max_version).unwrap_or(0) + 1
+        };
+
+        self.schema_import(subject, version, id, payload).await?;
+
+        Ok(RegisterSchemaResponse { id })
+    }
+
+    // stores a schema under the id and version it has in another registry, without checking its compatibility
+    pub async fn schema_import(&self, subject: &str, version: i32, id: i64, payload: &SchemaPayload) -> Result<(), AppError> {
+        let parsed_schema = self.parse(&payload.schema_type, &payload.schema, &payload.references).await?;
+        let fingerprints = parsed_schema.fingerprints();
+
+        match self.repository.schema_import(subject, version, id, &fingerprints, payload).await? {
+            SchemaImport::Imported => Ok(()),
+            SchemaImport::IdTaken => Err(AppError::OperationNotPermitted(format!("Overwrite new schema with id {} is not permitted.", id))),
+            SchemaImport::VersionTaken => Err(AppError::OperationNotPermitted(format!("Overwrite version {} of subject {} is not permitted.", version, subject))),
+            SchemaImport::OtherId(existing) => Err(AppError::IdDoesNotMatch(existing, id))
+        }
+    }
+
+    // a version another registry deleted, without the mode and reference checks of a delete, the records
+    // referencing it may only be deleted after it
+    pub async fn schema_import_delete(&self, subject: &str, version: i32) -> Result<(), AppError> {
+        if let Some(existing) = self.repository.schema_find_by_version(subject, version, false).await? {
+            self.repository.schema_soft_delete(subject, existing.id).await?;
+        }
+
+        Ok(())
+    }
+
+    // like schema_import_delete, for a version another registry deleted permanently
+    pub async fn schema_import_purge(&self, subject: &str, version: i32) -> Result<(), AppError> {
+        self.schema_import_delete(subject, version).await?;
+        self.repository.schema_permanent_delete(subject, version).await?;
+
+        Ok(())
+    }
+
+    // like schema_import_delete, for a subject
+    pub async fn subject_import_delete(&self, subject: &str) -> Result<(), AppError> {
+        if self.subject_named(subject).await?.is_some() {
+            self.repository.subject_soft_delete(subject).await?;
+        }
+
+        Ok(())
+    }
+
+    // replaces the config of a subject, soft deleted or not, an alias-only config reserves a subject that doesn't exist,
+    // false when there is no subject to set it on
+    pub async fn config_import(&self, subject: Option<&str>, config: &Config) -> Result<bool, AppError> {
+        let alias_only = config.alias.is_some() && *config == Config { alias: config.alias.clone(), ..Config::default() };
+
+        let subject_id = match subject {
+            Some(sub) => match self.repository.subject_find_deleted(sub).await? {
+                Some(found) => Some(found.id),
+                None if alias_only => Some(self.repository.subject_reserve(sub).await?.id),
+                None => return Ok(false)
             },
-            Compatibility::Forward => {
-                match schemas.first() {
-                    Some(versioned_schema) => {
-                        let db_schema = AvroSchema::parse_str(versioned_schema.schema.as_str())?;
-                        Ok(AvroSchemaCompatibility::can_read(&incoming, &db_schema))
-                    },
-                    None => Ok(true)
-                }
+            None => None
+        };
+
+        self.repository.config_set_subject(subject_id, config).await?;
+        Ok(true)
+    }
+
+    // like config_import, for a mode
+    pub async fn mode_import(&self, subject: Option<&str>, mode: &Mode) -> Result<bool, AppError> {
+        let subject_id = match subject {
+            Some(sub) => match self.repository.subject_find_deleted(sub).await? {
+                Some(found) => Some(found.id),
+                None => return Ok(false)
             },
-            Compatibility::ForwardTransitive => {
-                for s in schemas {
-                    let db_schema = AvroSchema::parse_str(s.schema.as_str())?;
-                    if !AvroSchemaCompatibility::can_read(&incoming, &db_schema) {
-                        return Ok(false)
-                    }
-                }
+            None => None
+        };
+
+        self.repository.mode_set_subject(subject_id, mode).await?;
+        Ok(true)
+    }
+
+    pub async fn effective_mode(&self, subject: &str) -> Result<Mode, AppError> {
+        let subject_mode = self.mode_get_subject(Some(subject)).await?;
+        let global_mode = self.mode_get_subject(None).await?;
 
-                return Ok(true)
+        Ok(subject_mode.or(global_mode).unwrap_or(Mode::ReadWrite))
+    }
+
+    async fn writable(&self, subject: &str) -> Result<(), AppError> {
+        match self.effective_mode(subject).await? {
+            Mode::ReadOnly | Mode::ReadOnlyOverride => Err(AppError::OperationNotPermitted(format!("Subject {} is in read-only mode", subject))),
+            _ => Ok(())
+        }
+    }
+
+    // the global config with the built-in defaults for the fields it doesn't set
+    pub async fn global_config(&self) -> Result<Config, AppError> {
+        let global_config = self.config_get_subject(None).await?.unwrap_or_default();
+        Ok(global_config.or(Config::defaults()))
+    }
+
+    // every field as set on the subject, else on the global config, else the built-in default
+    pub async fn effective_config(&self, subject: &str) -> Result<Config, AppError> {
+        let subject_config = self.config_get_subject(Some(subject)).await?.unwrap_or_default();
+        Ok(subject_config.or(self.global_config().await?))
+    }
+
+    pub async fn effective_compatibility(&self, subject: &str) -> Result<Compatibility, AppError> {
+        Ok(self.effective_config(subject).await?.compatibility())
+    }
+
+    // checks `incoming` against the versions its registration would be checked against
+    pub async fn check_subject_compatibility(&self, subject: &str, incoming: &SchemaPayload) -> Result<Vec<String>, AppError> {
+        let subject = &self.subject_alias(subject).await?;
+        let parsed_schema = self.parse(&incoming.schema_type, &incoming.schema, &incoming.references).await?;
+        let compatibility = self.effective_compatibility(subject).await?;
+        let subject_schemas = self.subject_schemas(subject).await?;
+
+        self.schema_compatibility(&subject_schemas, &parsed_schema, compatibility).await
+    }
+
+    // the reasons why `incoming` breaks `compatibility` with the given versions, empty when it does not
+    #[async_recursion]
+    pub async fn schema_compatibility(&self, schemas: &Vec<VersionedSchema>, incoming: &ParsedSchema, compatibility: Compatibility) -> Result<Vec<String>, AppError> {
+        let mut res = vec![];
+
+        match compatibility {
+            Compatibility::Backward | Compatibility::BackwardTransitive | Compatibility::Forward | Compatibility::ForwardTransitive => {
+                let checked = match compatibility {
+                    Compatibility::Backward | Compatibility::Forward => &schemas[..schemas.len().min(1)],
+                    _ => &schemas[..]
+                };
+
+                for s in checked {
+                    let db_schema = self.parse_stored(s.id, &s.schema_type, &s.schema).await?;
+                    let incompatibilities = match compatibility {
+                        Compatibility::Backward | Compatibility::BackwardTransitive => ParsedSchema::incompatibilities(&db_schema, incoming),
+                        _ => ParsedSchema::incompatibilities(incoming, &db_schema)
+                    };
+                    res.extend(incompatibilities.iter().map(|x| incompatibility_message(x, s.version, compatibility)));
+                }
             },
             Compatibility::Full => {
-                let backward = self.schema_compatibility(&schemas, &incoming, Compatibility::Backward).await?;
-                let forward = self.schema_compatibility(&schemas, &incoming, Compatibility::Forward).await?;
-                Ok(backward && forward)
+                res.extend(self.schema_compatibility(schemas, incoming, Compatibility::Backward).await?);
+                res.extend(self.schema_compatibility(schemas, incoming, Compatibility::Forward).await?);
             },
             Compatibility::FullTransitive => {
-                let backward = self.schema_compatibility(&schemas, &incoming, Compatibility::BackwardTransitive).await?;
-                let forward = self.schema_compatibility(&schemas, &incoming, Compatibility::ForwardTransitive).await?;
-                Ok(backward && forward)
+                res.extend(self.schema_compatibility(schemas, incoming, Compatibility::BackwardTransitive).await?);
+                res.extend(self.schema_compatibility(schemas, incoming, Compatibility::ForwardTransitive).await?);
             },
-            Compatibility::None => Ok(true)
+            Compatibility::None => ()
         }
+
+        Ok(res)
     }
 
-    pub async fn subject_versions(&self, subject: &String) -> Result<Vec<i32>, AppError> {
-        let res = self.repository.subject_versions(&subject).await?;
+    pub async fn subject_versions(&self, subject: &str, params: &VersionsParams) -> Result<Vec<i32>, AppError> {
+        let subject = &self.subject_alias(subject).await?;
+        self.subject_known(subject, params.deleted).await?;
+        let (offset, limit) = page(params.offset, params.limit);
+        let res = self.repository.subject_versions(subject, params.deleted, offset, limit).await?;
         Ok(res)
     }
 
-    pub async fn subject_find(&self, subject: &String) -> Result<Option<Subject>, AppError> {
+    // the subject the subject's own `alias` config points to, or the subject itself, aliases don't chain
+    // an alias needs no versions of its own, so it is read whether or not the subject is live
+    pub async fn subject_alias(&self, subject: &str) -> Result<String, AppError> {
+        let alias = match self.repository.subject_find_deleted(subject).await? {
+            Some(found) => self.repository.config_get_subject(Some(found.id)).await?.and_then(|x| x.alias),
+            None => None
+        };
+
+        Ok(alias.unwrap_or(subject.to_string()))
+    }
+
+    // deletes through an alias would hit the alias subject's own versions rather than the ones reads show
+    async fn unaliased(&self, subject: &str) -> Result<(), AppError> {
+        match self.subject_alias(subject).await? {
+            alias if alias != subject => Err(AppError::OperationNotPermitted(format!("Subject {} is an alias of {}, remove the alias to delete or undelete its own versions", subject, alias))),
+            _ => Ok(())
+        }
+    }
+
+    // reads and registrations through an alias go to the subject it points to
+    pub async fn subject_find(&self, subject: &str) -> Result<Option<Subject>, AppError> {
+        let subject = self.subject_alias(subject).await?;
         let res = self.repository.subject_find(&subject).await?;
         Ok(res)
     }
 
-    pub async fn subject_all(&self) -> Result<Vec<Subject>, AppError> {
-        let res = self.repository.subject_all().await?;
+    pub async fn subject_required(&self, subject: &str) -> Result<Subject, AppError> {
+        self.subject_find(subject).await?.ok_or(AppError::SubjectNotFound(subject.to_string()))
+    }
+
+    // the subject by its own name, whether or not it is an alias, for deletes, configs and modes
+    pub async fn subject_named(&self, subject: &str) -> Result<Option<Subject>, AppError> {
+        let res = self.repository.subject_find(subject).await?;
+        Ok(res)
+    }
+
+    pub async fn subject_named_required(&self, subject: &str) -> Result<Subject, AppError> {
+        self.subject_named(subject).await?.ok_or(AppError::SubjectNotFound(subject.to_string()))
+    }
+
+    // like subject_named_required, `deleted` also accepts soft deleted subjects
+    pub async fn subject_known(&self, subject: &str, deleted: bool) -> Result<(), AppError> {
+        let known = match deleted {
+            true => self.repository.subject_deleted(subject).await?.is_some(),
+            false => self.subject_named(subject).await?.is_some()
+        };
+
+        match known {
+            true => Ok(()),
+            false => Err(AppError::SubjectNotFound(subject.to_string()))
+        }
+    }
+
+    pub async fn subject_all(&self, params: &SubjectsParams) -> Result<Vec<Subject>, AppError> {
+        let (offset, limit) = page(params.offset, params.limit);
+        let res = self.repository.subject_all(&params.subject_prefix, params.deleted, offset, limit).await?;
         Ok(res)
     }
 
-    pub async fn subject_schemas(&self, subject: &String) -> Result<Vec<VersionedSchema>, AppError> {
-        let res = self.repository.subject_schemas(&subject).await?;
+    pub async fn subject_schemas(&self, subject: &str) -> Result<Vec<VersionedSchema>, AppError> {
+        let res = self.repository.subject_schemas(subject).await?;
         Ok(res)
     }
 
-    pub async fn config_get_subject(&self, subject: Option<&String>) -> Result<Option<SchemaCompatibility>, AppError> {
+    // the versions subject_schemas leaves out, soft deleted on their own or with their subject
+    pub async fn subject_deleted_schemas(&self, subject: &str) -> Result<Vec<SubjectSchema>, AppError> {
+        let live = self.repository.subject_versions(subject, false, 0, None).await?;
+
+        let mut res = vec![];
+        for version in self.repository.subject_versions(subject, true, 0, None).await? {
+            if live.contains(&version) {
+                continue
+            }
+            if let Some(schema) = self.repository.schema_find_by_version(subject, version, true).await? {
+                res.push(schema);
+            }
+        }
 
+        Ok(res)
+    }
+
+    // the config set on the subject, soft deleted or not, or the global one, None for subjects that don't exist
+    pub async fn config_get_subject(&self, subject: Option<&str>) -> Result<Option<Config>, AppError> {
         let subject_id = match subject {
-            Some(sub) => self.subject_find(sub).await?.map(|x| x.id),
+            Some(sub) => match self.repository.subject_find_deleted(sub).await? {
+                Some(found) => Some(found.id),
+                None => return Ok(None)
+            },
             None => None
         };
 
@@ -163,47 +598,109 @@ impl <R : Repository + Send + Sync> Service<R> {
         Ok(res)
     }
 
-    pub async fn config_set_subject(&self, subject: Option<&String>, compatibility: &Compatibility) -> Result<(), AppError> {
+    // an unknown subject is rejected, its config would otherwise land on the global one, unless the config only
+    // sets an alias, which can name a subject before anything is registered under it
+    // the fields `update` leaves unset keep their stored value, returns the config as stored
+    pub async fn config_set_subject(&self, subject: Option<&str>, update: Config) -> Result<Config, AppError> {
+        let alias_only = update.alias.is_some() && update == Config { alias: update.alias.clone(), ..Config::default() };
+
         let subject_id = match subject {
-            Some(sub) => self.subject_find(sub).await?.map(|x| x.id),
+            Some(sub) => match self.subject_named(sub).await? {
+                Some(found) => Some(found.id),
+                None if alias_only => Some(self.repository.subject_reserve(sub).await?.id),
+                None => return Err(AppError::SubjectNotFound(sub.to_string()))
+            },
             None => None
         };
 
-        let _ = self.repository.config_set_subject(subject_id, &compatibility).await?;
+        let existing = self.repository.config_get_subject(subject_id).await?.unwrap_or_default();
+        let config = update.or(existing);
+        self.repository.config_set_subject(subject_id, &config).await?;
+
+        Ok(config)
+    }
+
+    pub async fn config_delete_subject(&self, subject: Option<&str>) -> Result<Option<Config>, AppError> {
+        let subject_id = match subject {
+            Some(sub) => Some(self.repository.subject_find_deleted(sub).await?.ok_or(AppError::SubjectNotFound(sub.to_string()))?.id),
+            None => None
+        };
+
+        let res = self.repository.config_delete_subject(subject_id).await?;
+        Ok(res)
+    }
+
+    // the mode set on the subject, soft deleted or not, or the global one, None for subjects that don't exist
+    pub async fn mode_get_subject(&self, subject: Option<&str>) -> Result<Option<Mode>, AppError> {
+        let subject_id = match subject {
+            Some(sub) => match self.repository.subject_find_deleted(sub).await? {
+                Some(found) => Some(found.id),
+                None => return Ok(None)
+            },
+            None => None
+        };
+
+        let res = self.repository.mode_get_subject(subject_id).await?;
+        Ok(res)
+    }
+
+    pub async fn mode_set_subject(&self, subject: Option<&str>, mode: &Mode) -> Result<(), AppError> {
+        let subject_id = match subject {
+            Some(sub) => Some(self.subject_named_required(sub).await?.id),
+            None => None
+        };
+
+        self.repository.mode_set_subject(subject_id, mode).await?;
 
         Ok(())
     }
 
-    pub async fn version_id(&self, subject: &String, version_id: &VersionId) -> Result<Option<i32>, AppError> {
+    pub async fn version_id(&self, subject: &str, version_id: &VersionId, deleted: bool) -> Result<Option<i32>, AppError> {
+        self.subject_known(subject, deleted).await?;
+
         match version_id {
+            VersionId::Latest if deleted => {
+                let res = self.repository.subject_versions(subject, true, 0, None).await?;
+                Ok(res.into_iter().max())
+            },
             VersionId::Latest => {
-                let res = self.repository.max_version(&subject).await?;
+                let res = self.repository.max_version(subject, false).await?;
                 Ok(res.and_then(|x| x.max_version))
             },
             VersionId::Version(version) => Ok(Some(*version))
         }
     }
 
-    pub async fn check_compatibility(&self, subject: &String, version_id: &VersionId, incoming: &String) -> Result<Compatibility, AppError> {
+    pub async fn check_compatibility(&self, subject: &str, version_id: &VersionId, incoming: &SchemaPayload) -> Result<(Compatibility, Vec<String>), AppError> {
         let schema_record = self
-            .schema_find_by_version(&subject, &version_id)
+            .schema_find_by_version(subject, version_id, false)
             .await?
-            .ok_or(AppError::SchemaNotFound(subject.clone(), version_id.clone()))?;
+            .ok_or(AppError::VersionNotFound(subject.to_string(), version_id.clone()))?;
 
-        let db_schema = AvroSchema::parse_str(schema_record.schema.as_str())?;
-        let incoming_schema = AvroSchema::parse_str(incoming.as_str())?;
+        let db_schema = self.parse_stored(schema_record.id, &schema_record.schema_type, &schema_record.schema).await?;
+        let incoming_schema = self.parse(&incoming.schema_type, &incoming.schema, &incoming.references).await?;
         
-        let backward = AvroSchemaCompatibility::can_read(&db_schema, &incoming_schema);
-        let forward = AvroSchemaCompatibility::can_read(&incoming_schema, &db_schema);
-
-        if backward && forward {
-            return Ok(Compatibility::Full);
-        } else if backward {
-            return Ok(Compatibility::Backward);
-        } else if forward {
-            return Ok(Compatibility::Forward);
-        }
+        let backward: Vec<String> = ParsedSchema::incompatibilities(&db_schema, &incoming_schema)
+            .iter()
+            .map(|x| incompatibility_message(x, schema_record.version, Compatibility::Backward))
+            .collect();
+        let forward: Vec<String> = ParsedSchema::incompatibilities(&incoming_schema, &db_schema)
+            .iter()
+            .map(|x| incompatibility_message(x, schema_record.version, Compatibility::Forward))
+            .collect();
+
+        let compatibility = match (backward.is_empty(), forward.is_empty()) {
+            (true, true) => Compatibility::Full,
+            (true, false) => Compatibility::Backward,
+            (false, true) => Compatibility::Forward,
+            (false, false) => Compatibility::None
+        };
 
-        return Ok(Compatibility::None)
+        Ok((compatibility, [backward, forward].concat()))
     }
-}
\ No newline at end of file
+}
+
+fn incompatibility_message(incompatibility: &Incompatibility, version: i32, compatibility: Compatibility) -> String {
+    format!("{} at '{}', reader type: {}, writer type: {}, version: {}, compatibility: {}",
+        incompatibility.reason, incompatibility.path, incompatibility.reader_type, incompatibility.writer_type, version, compatibility.as_str())
+}
@@ -0,0 +1,559 @@
+use async_trait::async_trait;
+use sqlx::{Error, Sqlite, SqliteConnection, SqlitePool, Transaction};
+
+use crate::data::*;
+use crate::repository::Repository;
+
+// the same tables and semantics as PgRepository on top of sqlite, see migrations/sqlite
+// the queries are checked at runtime, as `query!` can only check one database at compile time
+#[derive(Clone)]
+pub struct SqliteRepository { pub pool: SqlitePool }
+
+impl SqliteRepository {
+
+    // what BEGIN IMMEDIATE does, which sqlx 0.7 has no way to issue: the transaction takes the write lock before its first
+    // read, so a concurrent writer makes it wait out the busy timeout, rather than fail with SQLITE_BUSY on its first write
+    async fn begin_immediate(&self) -> Result<Transaction<'static, Sqlite>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let _ = sqlx::query(r#"DELETE FROM subjects WHERE 0"#)
+            .execute(&mut *tx)
+            .await?;
+        Ok(tx)
+    }
+
+    async fn subject_upsert(conn: &mut SqliteConnection, subject: &str) -> Result<Subject, Error> {
+        sqlx::query_as::<_, Subject>(r#"INSERT INTO subjects (name, created_at) VALUES (?1, CURRENT_TIMESTAMP) ON CONFLICT (name) DO UPDATE SET deleted_at = null RETURNING id, name"#)
+            .bind(subject)
+            .fetch_one(conn)
+            .await
+    }
+
+    // AUTOINCREMENT continues after the largest id inserted, explicit ones included
+    async fn insert_schema(conn: &mut SqliteConnection, id: Option<i64>, fingerprints: &Fingerprints, schema: &str, schema_type: &SchemaType) -> Result<i64, Error> {
+        let id = match id {
+            Some(id) => {
+                let _ = sqlx::query(r#"INSERT INTO schemas (id, fingerprint, lookup_fingerprint, json, schema_type, created_at) VALUES (?1, ?2, ?3, ?4, ?5, CURRENT_TIMESTAMP) ON CONFLICT (id) DO UPDATE SET deleted_at = null"#)
+                    .bind(id)
+                    .bind(&fingerprints.fingerprint)
+                    .bind(&fingerprints.lookup_fingerprint)
+                    .bind(schema)
+                    .bind(schema_type.as_str())
+                    .execute(conn)
+                    .await?;
+
+                id
+            },
+            None => sqlx::query_as::<_, (i64,)>(r#"INSERT INTO schemas (fingerprint, lookup_fingerprint, json, schema_type, created_at) VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP) ON CONFLICT (fingerprint) DO UPDATE SET deleted_at = null RETURNING id"#)
+                .bind(&fingerprints.fingerprint)
+                .bind(&fingerprints.lookup_fingerprint)
+                .bind(schema)
+                .bind(schema_type.as_str())
+                .fetch_one(conn)
+                .await?
+                .0
+        };
+
+        Ok(id)
+    }
+
+    // schemas still used by a version of any subject, soft deleted or not, are kept
+    async fn delete_unused_schema(conn: &mut SqliteConnection, schema_id: i64) -> Result<(), Error> {
+        let _ = sqlx::query(r#"DELETE FROM schemas WHERE id = ?1 AND NOT EXISTS (SELECT 1 FROM schema_versions WHERE schema_id = ?1)"#)
+            .bind(schema_id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn insert_schema_references(conn: &mut SqliteConnection, schema_id: i64, references: &[SchemaReference]) -> Result<(), Error> {
+        for reference in references {
+            let _ = sqlx::query(r#"INSERT INTO schema_references (schema_id, name, subject, version) VALUES (?1, ?2, ?3, ?4) ON CONFLICT (schema_id, name) DO NOTHING"#)
+                .bind(schema_id)
+                .bind(&reference.name)
+                .bind(&reference.subject)
+                .bind(reference.version)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // a version number that is taken, even by a soft deleted version, fails as a unique violation
+    async fn insert_schema_version(conn: &mut SqliteConnection, version: i32, subject_id: i64, schema_id: i64) -> Result<(), Error> {
+        let _ = sqlx::query(r#"INSERT INTO schema_versions (version, subject_id, schema_id) VALUES (?1, ?2, ?3)"#)
+            .bind(version)
+            .bind(subject_id)
+            .bind(schema_id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Repository for SqliteRepository {
+
+    async fn schema_find_by_id(&self, id: i64) -> Result<Option<SchemaPayload>, Error> {
+        let res = sqlx::query_as::<_, (String, String)>(r#"select json, schema_type from schemas where id = ?1"#)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match res {
+            Some((schema, schema_type)) => {
+                let references = self.schema_references(id).await?;
+                Ok(Some(SchemaPayload { schema, schema_type: schema_type.into(), references, id: None, version: None }))
+            },
+            None => Ok(None)
+        }
+    }
+
+    async fn schema_soft_delete(&self, subject: &str, schema_id: i64) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let affected = sqlx::query(r#"UPDATE schema_versions SET deleted_at = CURRENT_TIMESTAMP WHERE schema_id = ?1 AND deleted_at IS NULL AND subject_id = (SELECT id FROM subjects WHERE name = ?2)"#)
+            .bind(schema_id)
+            .bind(subject)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        let _ = sqlx::query(r#"UPDATE schemas SET deleted_at = CURRENT_TIMESTAMP WHERE id = ?1 AND NOT EXISTS (SELECT 1 FROM schema_versions WHERE schema_id = ?1 AND deleted_at IS NULL)"#)
+            .bind(schema_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(affected)
+    }
+
+    async fn subject_soft_delete(&self, subject_name: &str) -> Result<Vec<i64>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let subject_record = sqlx::query_as::<_, (i64,)>(r#"UPDATE subjects SET deleted_at = CURRENT_TIMESTAMP WHERE name = ?1 RETURNING id"#)
+            .bind(subject_name)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let schema_ids = match subject_record {
+            Some((subject_id,)) => sqlx::query_as::<_, (i64,)>(r#"UPDATE schema_versions SET deleted_at = CURRENT_TIMESTAMP WHERE subject_id = ?1 AND deleted_at IS NULL RETURNING schema_id"#)
+                .bind(subject_id)
+                .fetch_all(&mut *tx)
+                .await?
+                .into_iter()
+                .map(|(x,)| x)
+                .collect(),
+            None => vec![]
+        };
+
+        tx.commit().await?;
+
+        Ok(schema_ids)
+    }
+
+    async fn subject_deleted(&self, subject: &str) -> Result<Option<bool>, Error> {
+        let res = sqlx::query_as::<_, (bool,)>(r#"SELECT deleted_at IS NOT NULL FROM subjects WHERE name = ?1"#)
+            .bind(subject)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(|(x,)| x))
+    }
+
+    async fn schema_version_deleted(&self, subject: &str, version: i32) -> Result<Option<bool>, Error> {
+        let res = sqlx::query_as::<_, (bool,)>(r#"SELECT sv.deleted_at IS NOT NULL FROM schema_versions sv INNER JOIN subjects sub ON sv.subject_id = sub.id WHERE sub.name = ?1 AND sv.version = ?2"#)
+            .bind(subject)
+            .bind(version)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(|(x,)| x))
+    }
+
+    async fn schema_permanent_delete(&self, subject: &str, version: i32) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let removed = sqlx::query_as::<_, (i64,)>(r#"DELETE FROM schema_versions WHERE version = ?1 AND deleted_at IS NOT NULL AND subject_id = (SELECT id FROM subjects WHERE name = ?2) RETURNING schema_id"#)
+            .bind(version)
+            .bind(subject)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let affected = match removed {
+            Some((schema_id,)) => {
+                Self::delete_unused_schema(&mut tx, schema_id).await?;
+                1
+            },
+            None => 0
+        };
+
+        tx.commit().await?;
+
+        Ok(affected)
+    }
+
+    async fn subject_permanent_delete(&self, subject: &str) -> Result<Vec<i64>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let schema_ids: Vec<i64> = sqlx::query_as::<_, (i64,)>(r#"DELETE FROM schema_versions WHERE subject_id = (SELECT id FROM subjects WHERE name = ?1 AND deleted_at IS NOT NULL) RETURNING schema_id"#)
+            .bind(subject)
+            .fetch_all(&mut *tx)
+            .await?
+            .into_iter()
+            .map(|(x,)| x)
+            .collect();
+
+        for schema_id in &schema_ids {
+            Self::delete_unused_schema(&mut tx, *schema_id).await?;
+        }
+
+        let _ = sqlx::query(r#"DELETE FROM subjects WHERE name = ?1 AND deleted_at IS NOT NULL"#)
+            .bind(subject)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(schema_ids)
+    }
+
+    async fn schema_undelete(&self, subject: &str, version: i32) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let restored = sqlx::query_as::<_, (i64,)>(r#"UPDATE schema_versions SET deleted_at = null WHERE version = ?1 AND deleted_at IS NOT NULL AND subject_id = (SELECT id FROM subjects WHERE name = ?2) RETURNING schema_id"#)
+            .bind(version)
+            .bind(subject)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let affected = match restored {
+            Some((schema_id,)) => {
+                let _ = sqlx::query(r#"UPDATE schemas SET deleted_at = null WHERE id = ?1"#)
+                    .bind(schema_id)
+                    .execute(&mut *tx)
+                    .await?;
+                let _ = sqlx::query(r#"UPDATE subjects SET deleted_at = null WHERE name = ?1"#)
+                    .bind(subject)
+                    .execute(&mut *tx)
+                    .await?;
+                1
+            },
+            None => 0
+        };
+
+        tx.commit().await?;
+
+        Ok(affected)
+    }
+
+    async fn subject_undelete(&self, subject: &str) -> Result<Vec<i64>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let subject_record = sqlx::query_as::<_, (i64,)>(r#"UPDATE subjects SET deleted_at = null WHERE name = ?1 RETURNING id"#)
+            .bind(subject)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let schema_ids: Vec<i64> = match subject_record {
+            Some((subject_id,)) => sqlx::query_as::<_, (i64,)>(r#"UPDATE schema_versions SET deleted_at = null WHERE subject_id = ?1 AND deleted_at IS NOT NULL RETURNING schema_id"#)
+                .bind(subject_id)
+                .fetch_all(&mut *tx)
+                .await?
+                .into_iter()
+                .map(|(x,)| x)
+                .collect(),
+            None => vec![]
+        };
+
+        for schema_id in &schema_ids {
+            let _ = sqlx::query(r#"UPDATE schemas SET deleted_at = null WHERE id = ?1"#)
+                .bind(schema_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(schema_ids)
+    }
+
+    async fn schema_find_by_version(&self, subject: &str, version: i32, deleted: bool) -> Result<Option<SubjectSchema>, Error> {
+        sqlx::query_as::<_, SubjectSchema>(r#"select sub.name as subject, sv.version as version, sch.id as id, sch.json as schema, sch.schema_type as schema_type from schemas sch inner join schema_versions sv on sch.id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id where (?3 or (sub.deleted_at is null and sch.deleted_at is null and sv.deleted_at is null)) and sv.version = ?1 and sub.name = ?2"#)
+            .bind(version)
+            .bind(subject)
+            .bind(deleted)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn schema_find_by_schema(&self, subject: &str, fingerprint: &str, normalize: bool) -> Result<Option<SubjectSchema>, Error> {
+        sqlx::query_as::<_, SubjectSchema>(r#"select sub.name as subject, sv.version as version, sch.id as id, sch.json as schema, sch.schema_type as schema_type from schemas sch inner join schema_versions sv on sch.id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id where sub.deleted_at is null and sch.deleted_at is null and sv.deleted_at is null and (case when ?3 then sch.lookup_fingerprint else sch.fingerprint end) = ?1 and sub.name = ?2 order by sv.version limit 1"#)
+            .bind(fingerprint)
+            .bind(subject)
+            .bind(normalize)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn insert(&self, subject: &str, fingerprints: &Fingerprints, schema: &str, schema_type: &SchemaType, references: &[SchemaReference], max_version: i32) -> Result<Option<i64>, Error> {
+        let mut tx = self.begin_immediate().await?;
+
+        // the write lock is held until commit, which serializes version allocation
+        let subject_record = Self::subject_upsert(&mut tx, subject).await?;
+
+        let current = sqlx::query_as::<_, MaxVersion>(r#"select max(version) as max_version from schema_versions where subject_id = ?1"#)
+            .bind(subject_record.id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        if current.max_version.unwrap_or(0) != max_version {
+            tx.rollback().await?;
+            return Ok(None)
+        }
+
+        let schema_id = Self::insert_schema(&mut tx, None, fingerprints, schema, schema_type).await?;
+        Self::insert_schema_references(&mut tx, schema_id, references).await?;
+        Self::insert_schema_version(&mut tx, max_version + 1, subject_record.id, schema_id).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(schema_id))
+    }
+
+    async fn schema_import(&self, subject: &str, version: i32, id: i64, fingerprints: &Fingerprints, payload: &SchemaPayload) -> Result<SchemaImport, Error> {
+        let mut tx = self.begin_immediate().await?;
+
+        let stored = sqlx::query_as::<_, (String,)>(r#"select fingerprint from schemas where id = ?1"#)
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let version_schema = sqlx::query_as::<_, (i64,)>(r#"select sv.schema_id from schema_versions sv inner join subjects sub on sv.subject_id = sub.id where sub.name = ?1 and sv.version = ?2"#)
+            .bind(subject)
+            .bind(version)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let existing = sqlx::query_as::<_, (i64,)>(r#"select id from schemas where fingerprint = ?1"#)
+            .bind(&fingerprints.fingerprint)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        // a version keeps its schema, importing it again changes nothing
+        let found = match (stored, version_schema, existing) {
+            (Some((fingerprint,)), _, _) if fingerprint != fingerprints.fingerprint => Some(SchemaImport::IdTaken),
+            (_, Some((schema_id,)), _) if schema_id != id => Some(SchemaImport::VersionTaken),
+            (_, Some(_), _) => Some(SchemaImport::Imported),
+            (_, _, Some((existing_id,))) if existing_id != id => Some(SchemaImport::OtherId(existing_id)),
+            _ => None
+        };
+
+        if let Some(found) = found {
+            tx.rollback().await?;
+            return Ok(found)
+        }
+
+        let subject_record = Self::subject_upsert(&mut tx, subject).await?;
+
+        Self::insert_schema(&mut tx, Some(id), fingerprints, &payload.schema, &payload.schema_type).await?;
+        Self::insert_schema_references(&mut tx, id, &payload.references).await?;
+        Self::insert_schema_version(&mut tx, version, subject_record.id, id).await?;
+
+        tx.commit().await?;
+
+        Ok(SchemaImport::Imported)
+    }
+
+    async fn schema_fingerprints(&self) -> Result<Vec<StoredFingerprints>, Error> {
+        sqlx::query_as::<_, StoredFingerprints>(r#"select id, fingerprint, lookup_fingerprint from schemas order by id"#)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn schema_fingerprints_update(&self, id: i64, fingerprints: &Fingerprints) -> Result<(), Error> {
+        let _ = sqlx::query(r#"UPDATE schemas SET fingerprint = CASE WHEN EXISTS (SELECT 1 FROM schemas WHERE fingerprint = ?2 AND id <> ?1) THEN fingerprint ELSE ?2 END, lookup_fingerprint = ?3 WHERE id = ?1"#)
+            .bind(id)
+            .bind(&fingerprints.fingerprint)
+            .bind(&fingerprints.lookup_fingerprint)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn schema_references(&self, schema_id: i64) -> Result<Vec<SchemaReference>, Error> {
+        sqlx::query_as::<_, SchemaReference>(r#"select name, subject, version from schema_references where schema_id = ?1 order by id"#)
+            .bind(schema_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn schema_subjects(&self, schema_id: i64, deleted: bool) -> Result<Vec<String>, Error> {
+        let res = sqlx::query_as::<_, (String,)>(r#"select distinct sub.name from schema_versions sv inner join subjects sub on sv.subject_id = sub.id where (?2 or (sub.deleted_at is null and sv.deleted_at is null)) and sv.schema_id = ?1 order by sub.name"#)
+            .bind(schema_id)
+            .bind(deleted)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.into_iter().map(|(x,)| x).collect())
+    }
+
+    async fn schema_versions(&self, schema_id: i64, deleted: bool) -> Result<Vec<SubjectVersion>, Error> {
+        sqlx::query_as::<_, SubjectVersion>(r#"select sub.name as subject, sv.version as version from schema_versions sv inner join subjects sub on sv.subject_id = sub.id where (?2 or (sub.deleted_at is null and sv.deleted_at is null)) and sv.schema_id = ?1 order by sub.name, sv.version"#)
+            .bind(schema_id)
+            .bind(deleted)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    // a negative limit is no limit in sqlite
+    async fn schema_all(&self, subject_prefix: &str, latest_only: bool, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<SubjectSchema>, Error> {
+        sqlx::query_as::<_, SubjectSchema>(r#"select sub.name as subject, sv.version as version, sch.id as id, sch.json as schema, sch.schema_type as schema_type from schemas sch inner join schema_versions sv on sch.id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id where (?3 or (sub.deleted_at is null and sch.deleted_at is null and sv.deleted_at is null)) and substr(sub.name, 1, length(?1)) = ?1 and (not ?2 or sv.version = (select max(latest.version) from schema_versions latest where latest.subject_id = sv.subject_id and (?3 or latest.deleted_at is null))) order by sub.name, sv.version limit ?5 offset ?4"#)
+            .bind(subject_prefix)
+            .bind(latest_only)
+            .bind(deleted)
+            .bind(offset)
+            .bind(limit.unwrap_or(-1))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn schema_referenced_by(&self, subject: &str, version: i32) -> Result<Vec<i64>, Error> {
+        let res = sqlx::query_as::<_, (i64,)>(r#"select distinct sr.schema_id as schema_id from schema_references sr inner join schema_versions sv on sr.schema_id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id inner join schemas sch on sch.id = sr.schema_id where sub.deleted_at is null and sch.deleted_at is null and sv.deleted_at is null and sr.subject = ?1 and sr.version = ?2 order by sr.schema_id"#)
+            .bind(subject)
+            .bind(version)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.into_iter().map(|(x,)| x).collect())
+    }
+
+    async fn subject_versions(&self, subject: &str, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<i32>, Error> {
+        let res = sqlx::query_as::<_, (i32,)>(r#"SELECT version FROM subjects s INNER JOIN schema_versions sv ON s.id = sv.subject_id WHERE (?2 or (s.deleted_at is null and sv.deleted_at is null)) and s.name = ?1 ORDER BY version LIMIT ?4 OFFSET ?3"#)
+            .bind(subject)
+            .bind(deleted)
+            .bind(offset)
+            .bind(limit.unwrap_or(-1))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.into_iter().map(|(x,)| x).collect())
+    }
+
+    async fn subject_find(&self, subject: &str) -> Result<Option<Subject>, Error> {
+        sqlx::query_as::<_, Subject>(r#"SELECT id, name FROM subjects WHERE deleted_at is null and name = ?1"#).bind(subject).fetch_optional(&self.pool).await
+    }
+
+    async fn subject_find_deleted(&self, subject: &str) -> Result<Option<Subject>, Error> {
+        sqlx::query_as::<_, Subject>(r#"SELECT id, name FROM subjects WHERE name = ?1"#).bind(subject).fetch_optional(&self.pool).await
+    }
+
+    async fn subject_reserve(&self, subject: &str) -> Result<Subject, Error> {
+        sqlx::query_as::<_, Subject>(r#"INSERT INTO subjects (name, created_at, deleted_at) VALUES (?1, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP) ON CONFLICT (name) DO UPDATE SET name = excluded.name RETURNING id, name"#)
+            .bind(subject)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    async fn subject_all(&self, subject_prefix: &str, deleted: bool, offset: i64, limit: Option<i64>) -> Result<Vec<Subject>, Error> {
+        sqlx::query_as::<_, Subject>(r#"SELECT id, name FROM subjects WHERE (?2 or deleted_at is null) and substr(name, 1, length(?1)) = ?1 ORDER BY name LIMIT ?4 OFFSET ?3"#)
+            .bind(subject_prefix)
+            .bind(deleted)
+            .bind(offset)
+            .bind(limit.unwrap_or(-1))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn subject_schemas(&self, subject: &str) -> Result<Vec<VersionedSchema>, Error> {
+        sqlx::query_as::<_, VersionedSchema>(r#"select sv.version as version, sch.id as id, sch.json as schema, sch.schema_type as schema_type from schemas sch inner join schema_versions sv on sch.id = sv.schema_id inner join subjects sub on sv.subject_id = sub.id where sub.deleted_at is null and sch.deleted_at is null and sv.deleted_at is null and sub.name = ?1 order by sv.version desc"#)
+            .bind(subject)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn config_get_subject(&self, subject_id: Option<i64>) -> Result<Option<Config>, Error> {
+        let res = sqlx::query_as::<_, ConfigRecord>(r#"select compatibility, normalize, alias, compatibility_group, default_metadata, override_metadata, default_rule_set, override_rule_set from configs where subject_id is ?1"#)
+            .bind(subject_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(Config::from))
+    }
+
+    async fn config_set_subject(&self, subject_id: Option<i64>, config: &Config) -> Result<(), Error> {
+        let record = ConfigRecord::from(config);
+        let mut tx = self.pool.begin().await?;
+
+        // replaced rather than upserted, so the global config whose subject_id is null is a single row too
+        let _ = sqlx::query(r#"delete from configs where subject_id is ?1"#)
+            .bind(subject_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let _ = sqlx::query(r#"insert into configs (compatibility, normalize, alias, compatibility_group, default_metadata, override_metadata, default_rule_set, override_rule_set, created_at, updated_at, subject_id) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, ?9)"#)
+            .bind(record.compatibility)
+            .bind(record.normalize)
+            .bind(record.alias)
+            .bind(record.compatibility_group)
+            .bind(record.default_metadata)
+            .bind(record.override_metadata)
+            .bind(record.default_rule_set)
+            .bind(record.override_rule_set)
+            .bind(subject_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn config_delete_subject(&self, subject_id: Option<i64>) -> Result<Option<Config>, Error> {
+        let res = sqlx::query_as::<_, ConfigRecord>(r#"delete from configs where subject_id is ?1 returning compatibility, normalize, alias, compatibility_group, default_metadata, override_metadata, default_rule_set, override_rule_set"#)
+            .bind(subject_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(Config::from))
+    }
+
+    async fn max_version(&self, subject: &str, deleted: bool) -> Result<Option<MaxVersion>, Error> {
+        sqlx::query_as::<_, MaxVersion>(r#"select max(version) as max_version from schema_versions sv inner join subjects sub on sv.subject_id = sub.id where (?2 or sv.deleted_at is null) and sub.name = ?1"#)
+            .bind(subject)
+            .bind(deleted)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn mode_get_subject(&self, subject_id: Option<i64>) -> Result<Option<Mode>, Error> {
+        let res = sqlx::query_as::<_, SchemaMode>(r#"select mode from modes where subject_id is ?1"#)
+            .bind(subject_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(|x| x.mode))
+    }
+
+    async fn mode_set_subject(&self, subject_id: Option<i64>, mode: &Mode) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // replaced rather than upserted, so the global mode whose subject_id is null is a single row too
+        let _ = sqlx::query(r#"delete from modes where subject_id is ?1"#)
+            .bind(subject_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let _ = sqlx::query(r#"insert into modes (mode, created_at, updated_at, subject_id) values (?1, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, ?2)"#)
+            .bind(mode.as_str())
+            .bind(subject_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1828 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use serde_json::{json, Value};
+use sqlx::sqlite::SqlitePoolOptions;
+use tower::ServiceExt;
+
+use crate::app;
+use crate::data::SchemaType;
+use crate::json_schema::{JsonSchema, JsonSchemaProvider};
+use crate::log_repository::LogRepository;
+use crate::memory_repository::InMemoryRepository;
+use crate::schema::{ResolvedReference, SchemaProvider, SchemaProviders};
+use crate::service::Service;
+use crate::sqlite_repository::SqliteRepository;
+
+fn registry() -> Router {
+    app(Service { repository: InMemoryRepository::default(), providers: SchemaProviders::default() })
+}
+
+// every connection to sqlite::memory: opens its own database, hence the single connection
+async fn sqlite_registry() -> Router {
+    let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+    sqlx::migrate!("./migrations/sqlite").run(&pool).await.unwrap();
+
+    app(Service { repository: SqliteRepository { pool }, providers: SchemaProviders::default() })
+}
+
+fn log_registry(repository: LogRepository) -> Router {
+    app(Service { repository, providers: SchemaProviders::default() })
+}
+
+async fn call(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    send(app.clone(), method, uri, body).await
+}
+
+// takes the router by value so the future can be spawned
+async fn send(app: Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("Content-Type", "application/json")
+        .body(body.map(|x| Body::from(x.to_string())).unwrap_or_else(Body::empty))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let value = serde_json::from_slice(&bytes).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).to_string()));
+
+    (status, value)
+}
+
+async fn call_text(app: &Router, method: &str, uri: &str, body: &str) -> (StatusCode, String) {
+    let request = Request::builder().method(method).uri(uri).body(Body::from(body.to_string())).unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+    (status, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+fn employee(fields: Value) -> Value {
+    json!({ "schema": json!({ "type": "record", "name": "Employee", "namespace": "test", "fields": fields }).to_string() })
+}
+
+fn employee_v1() -> Value {
+    employee(json!([{ "name": "name", "type": "string" }]))
+}
+
+fn employee_v2() -> Value {
+    employee(json!([{ "name": "name", "type": "string" }, { "name": "age", "type": "int", "default": 0 }]))
+}
+
+fn employee_incompatible() -> Value {
+    employee(json!([{ "name": "name", "type": "int" }]))
+}
+
+async fn register(app: &Router, subject: &str, body: Value) -> Value {
+    let (status, res) = call(app, "POST", &format!("/subjects/{}/versions", subject), Some(body)).await;
+    assert_eq!(status, StatusCode::OK, "{}", res);
+    res
+}
+
+#[tokio::test]
+async fn register_schema_allocates_versions() {
+    let app = registry();
+
+    assert_eq!(register(&app, "employee", employee_v1()).await, json!({ "id": 1 }));
+    assert_eq!(register(&app, "employee", employee_v2()).await, json!({ "id": 2 }));
+
+    let (status, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(versions, json!([1, 2]));
+}
+
+#[tokio::test]
+async fn register_schema_returns_the_existing_id() {
+    let app = registry();
+
+    register(&app, "employee", employee_v1()).await;
+    assert_eq!(register(&app, "employee", employee_v1()).await, json!({ "id": 1 }));
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1]));
+}
+
+#[tokio::test]
+async fn register_schema_normalizes_the_layout() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+
+    // the same schema with its keys in another order, a namespaced name and an attribute-less type
+    let reordered = json!({ "schema": r#" { "fields": [ { "type": { "type": "string" }, "name": "name" } ], "name": "test.Employee", "type": "record" } "# });
+    assert_eq!(register(&app, "employee", reordered).await, json!({ "id": 1 }));
+
+    // docs, aliases and defaults are part of the schema
+    let documented = employee(json!([{ "name": "name", "type": "string", "doc": "the full name" }]));
+    assert_eq!(register(&app, "employee", documented.clone()).await, json!({ "id": 2 }));
+    let aliased = employee(json!([{ "name": "name", "type": "string", "aliases": ["fullName"] }]));
+    assert_eq!(register(&app, "employee", aliased).await, json!({ "id": 3 }));
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1, 2, 3]));
+
+    // a lookup with ?normalize=true leaves the docs out
+    let (status, _) = call(&app, "POST", "/subjects/person", Some(employee_v1())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    register(&app, "person", employee_v1()).await;
+    let (status, _) = call(&app, "POST", "/subjects/person", Some(documented.clone())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, res) = call(&app, "POST", "/subjects/person?normalize=true", Some(documented.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res["version"], json!(1));
+
+    // while a registration compares the stored form, also under a normalize config
+    call(&app, "PUT", "/config/person", Some(json!({ "normalize": true }))).await;
+    assert_eq!(register(&app, "person", documented.clone()).await, json!({ "id": 2 }));
+    let (_, versions) = call(&app, "GET", "/subjects/person/versions", None).await;
+    assert_eq!(versions, json!([1, 2]));
+
+    // unless the registration asks for ?normalize=true, which finds the first version leaving out the docs
+    let (_, res) = call(&app, "POST", "/subjects/person/versions?normalize=true", Some(documented)).await;
+    assert_eq!(res, json!({ "id": 1 }));
+
+    // and stores a new schema in its normalized form
+    let reordered = json!({ "schema": r#" { "fields": [ { "type": "int", "name": "age" } ], "name": "Team", "namespace": "test", "type": "record" } "# });
+    let (_, res) = call(&app, "POST", "/subjects/team/versions?normalize=true", Some(reordered)).await;
+    assert_eq!(res, json!({ "id": 4 }));
+    let (_, res) = call(&app, "GET", "/subjects/team/versions/1", None).await;
+    let normalized = json!({ "type": "record", "name": "test.Team", "fields": [{ "name": "age", "type": "int" }] });
+    assert_eq!(res["schema"], json!(normalized.to_string()));
+}
+
+#[test]
+fn normalized_form_only_qualifies_names() {
+    let schema = json!({ "type": "record", "name": "Employee", "namespace": "test", "fields": [
+        { "name": "tags", "type": { "type": "array", "items": "string" } },
+        { "name": "manager", "type": ["null", "Employee"] }
+    ] });
+    let parsed = SchemaProviders::default().parse(&SchemaType::default(), &schema.to_string(), &[]).unwrap();
+
+    let expected = json!({ "type": "record", "name": "test.Employee", "fields": [
+        { "name": "tags", "type": { "type": "array", "items": "string" } },
+        { "name": "manager", "type": ["null", "test.Employee"] }
+    ] });
+    assert_eq!(parsed.normalized_form(), expected.to_string());
+}
+
+#[test]
+fn json_schema_lists_each_referenced_document_once() {
+    // properties are visited in the order of their names
+    let schema = json!({ "type": "object", "properties": {
+        "a_home": { "$ref": "a.json" },
+        "b_work": { "$ref": "b.json" },
+        "c_billing": { "$ref": "a.json#/definitions/street" }
+    } });
+    let references: Vec<ResolvedReference> = ["a.json", "b.json"].iter()
+        .map(|x| ResolvedReference { name: x.to_string(), subject: x.to_string(), version: 1, schema: json!({ "definitions": { "street": { "type": "string" } } }).to_string() })
+        .collect();
+    let parsed = JsonSchema::parse_str(&schema.to_string(), &references).unwrap();
+
+    assert_eq!(JsonSchemaProvider.references(&parsed), vec!["a.json", "b.json"]);
+}
+
+#[tokio::test]
+async fn register_schema_shares_ids_across_subjects() {
+    let app = registry();
+
+    register(&app, "employee", employee_v1()).await;
+    assert_eq!(register(&app, "person", employee_v1()).await, json!({ "id": 1 }));
+
+    let (_, version) = call(&app, "GET", "/subjects/person/versions/1", None).await;
+    assert_eq!(version["id"], json!(1));
+}
+
+#[tokio::test]
+async fn register_schema_rejects_incompatible_schemas() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+
+    let (status, res) = call(&app, "POST", "/subjects/employee/versions", Some(employee_incompatible())).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(res["error_code"], json!(409));
+    assert!(!res["message"].as_str().unwrap().contains("details"));
+
+    let (_, res) = call(&app, "POST", "/subjects/employee/versions?verbose=true", Some(employee_incompatible())).await;
+    assert!(res["message"].as_str().unwrap().contains("at '/name', reader type: int, writer type: string, version: 1"));
+}
+
+#[tokio::test]
+async fn register_schema_rejects_invalid_schemas() {
+    let app = registry();
+
+    let (status, res) = call(&app, "POST", "/subjects/employee/versions", Some(json!({ "schema": "{\"type\": \"recorb\"}" }))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res["error_code"], json!(42201));
+
+    let (status, res) = call(&app, "POST", "/subjects/employee/versions", Some(json!({ "schema": "{}", "schemaType": "XML" }))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res["error_code"], json!(42201));
+}
+
+#[tokio::test]
+async fn register_schema_rejects_malformed_bodies() {
+    let app = registry();
+
+    let (status, res) = call(&app, "POST", "/subjects/employee/versions", Some(json!({ "schemas": "" }))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res["error_code"], json!(422));
+}
+
+#[tokio::test]
+async fn register_schema_serializes_concurrent_registrations() {
+    let app = registry();
+
+    let handles: Vec<_> = (0..8).map(|i| {
+        let app = app.clone();
+        let body = employee(json!([{ "name": "name", "type": "string" }, { "name": format!("field{}", i), "type": "int", "default": 0 }]));
+        tokio::spawn(async move { send(app, "POST", "/subjects/employee/versions", Some(body)).await })
+    }).collect();
+
+    let mut registered = 0;
+    for handle in handles {
+        let (status, _) = handle.await.unwrap();
+        assert!(status == StatusCode::OK || status == StatusCode::CONFLICT);
+        if status == StatusCode::OK {
+            registered += 1;
+        }
+    }
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!((1..=registered).collect::<Vec<i32>>()));
+}
+
+#[tokio::test]
+async fn register_schema_registers_a_schema_once_under_concurrent_registrations() {
+    let app = sqlite_registry().await;
+    register(&app, "employee", employee_v1()).await;
+
+    let handles: Vec<_> = (0..8).map(|_| {
+        let app = app.clone();
+        tokio::spawn(async move { send(app, "POST", "/subjects/employee/versions", Some(employee_v2())).await })
+    }).collect();
+
+    for handle in handles {
+        assert_eq!(handle.await.unwrap(), (StatusCode::OK, json!({ "id": 2 })));
+    }
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1, 2]));
+}
+
+#[tokio::test]
+async fn register_schema_supports_protobuf() {
+    let app = registry();
+    let schema = |fields: &str| json!({ "schemaType": "PROTOBUF", "schema": format!("syntax = \"proto3\"; message Employee {{ {} }}", fields) });
+
+    register(&app, "employee", schema("string name = 1;")).await;
+    register(&app, "employee", schema("string name = 1; int32 age = 2;")).await;
+
+    let (status, _) = call(&app, "POST", "/subjects/employee/versions", Some(schema("int64 name = 1;"))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (_, version) = call(&app, "GET", "/subjects/employee/versions/latest", None).await;
+    assert_eq!(version["schemaType"], json!("PROTOBUF"));
+}
+
+async fn protobuf_messages(app: &Router, subject: &str, schema: &str) -> Vec<String> {
+    let body = json!({ "schemaType": "PROTOBUF", "schema": schema });
+    let (_, res) = call(app, "POST", &format!("/compatibility/subjects/{}/versions", subject), Some(body)).await;
+    res["messages"].as_array().unwrap().iter().map(|x| x.as_str().unwrap().to_string()).collect()
+}
+
+#[tokio::test]
+async fn protobuf_compatibility_checks_fields() {
+    let app = registry();
+    let schema = |fields: &str| format!("syntax = \"proto2\"; enum Role {{ STAFF = 0; }} message Team {{ optional string name = 1; }} message Employee {{ {} }}", fields);
+
+    register(&app, "employee", json!({ "schemaType": "PROTOBUF", "schema": schema("required string name = 1; optional Role role = 2;") })).await;
+
+    assert!(protobuf_messages(&app, "employee", &schema("required string name = 1; optional Role role = 2; optional int32 age = 3;")).await.is_empty());
+
+    let res = protobuf_messages(&app, "employee", &schema("required string title = 1; optional Role role = 2;")).await;
+    assert!(res.iter().any(|x| x.contains("the field number was reused by a field of another name")), "{:?}", res);
+
+    let res = protobuf_messages(&app, "employee", &schema("required string name = 1; optional Team role = 2;")).await;
+    assert!(res.iter().any(|x| x.contains("the field's type has another wire encoding")), "{:?}", res);
+
+    let res = protobuf_messages(&app, "employee", &schema("optional Role role = 2;")).await;
+    assert!(res.iter().any(|x| x.contains("a required field was removed")), "{:?}", res);
+
+    let res = protobuf_messages(&app, "employee", &schema("required string name = 1; optional Role role = 2; required int32 age = 3;")).await;
+    assert!(res.iter().any(|x| x.contains("a required field was added")), "{:?}", res);
+}
+
+#[tokio::test]
+async fn protobuf_compatibility_checks_enum_values() {
+    let app = registry();
+    let schema = |values: &str| format!("syntax = \"proto3\"; enum Role {{ {} }} message Employee {{ string name = 1; Role role = 2; }}", values);
+
+    register(&app, "employee", json!({ "schemaType": "PROTOBUF", "schema": schema("STAFF = 0; MANAGER = 1;") })).await;
+
+    assert!(protobuf_messages(&app, "employee", &schema("STAFF = 0; MANAGER = 1; DIRECTOR = 2;")).await.is_empty());
+
+    let res = protobuf_messages(&app, "employee", &schema("STAFF = 0;")).await;
+    assert!(res.iter().any(|x| x.contains("the enum value was removed")), "{:?}", res);
+
+    let res = protobuf_messages(&app, "employee", &schema("STAFF = 0; MANAGER = 2;")).await;
+    assert!(res.iter().any(|x| x.contains("the enum value was renumbered")), "{:?}", res);
+}
+
+#[tokio::test]
+async fn register_schema_supports_json_schema() {
+    let app = registry();
+    let schema = |value: Value| json!({ "schemaType": "JSON", "schema": value.to_string() });
+
+    register(&app, "employee", schema(json!({ "type": "object", "properties": { "name": { "type": "string" } } }))).await;
+
+    let (status, _) = call(&app, "POST", "/subjects/employee/versions", Some(schema(json!({ "type": "object", "properties": { "name": { "type": "integer" } } })))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn json_schema_compatibility_follows_the_content_model() {
+    let app = registry();
+    let schema = |value: Value| json!({ "schemaType": "JSON", "schema": value.to_string() });
+    let compatible = |subject: &str, value: Value| {
+        let (app, uri) = (app.clone(), format!("/compatibility/subjects/{}/versions", subject));
+        async move { call(&app, "POST", &uri, Some(schema(value))).await.1["is_compatible"].clone() }
+    };
+
+    // a property added to an open content model may have held anything before, a closed one had none
+    register(&app, "open", schema(json!({ "type": "object", "properties": { "name": { "type": "string" } } }))).await;
+    register(&app, "closed", schema(json!({ "type": "object", "properties": { "name": { "type": "string" } }, "additionalProperties": false }))).await;
+    let added = json!({ "type": "object", "properties": { "name": { "type": "string" }, "age": { "type": "integer" } } });
+    assert_eq!(compatible("open", added.clone()).await, json!(false));
+    assert_eq!(compatible("closed", added).await, json!(true));
+
+    // a property the reader requires has to be required by the writer, unless it has a default
+    let required = json!({ "type": "object", "properties": { "name": { "type": "string" } }, "required": ["name"] });
+    assert_eq!(compatible("open", required).await, json!(false));
+    let defaulted = json!({ "type": "object", "properties": { "name": { "type": "string", "default": "" } }, "required": ["name"] });
+    assert_eq!(compatible("open", defaulted).await, json!(true));
+
+    // the keywords next to a $ref are compared along with what it points to
+    let address = |extra: Value| {
+        let mut home = json!({ "$ref": "#/$defs/address" });
+        home.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        json!({ "type": "object", "$defs": { "address": { "type": "object", "properties": { "street": { "type": "string" } } } }, "properties": { "home": home } })
+    };
+    register(&app, "person", schema(address(json!({ "description": "where they live" })))).await;
+    assert_eq!(compatible("person", address(json!({ "required": ["street"] }))).await, json!(false));
+    register(&app, "resident", schema(address(json!({ "required": ["street"] })))).await;
+    assert_eq!(compatible("resident", address(json!({ "required": ["street"] }))).await, json!(true));
+}
+
+#[tokio::test]
+async fn register_schema_requires_a_reference_per_import() {
+    let app = registry();
+    register(&app, "address", json!({ "schemaType": "PROTOBUF", "schema": "syntax = \"proto3\"; message Address { string street = 1; }" })).await;
+    register(&app, "address_json", json!({ "schemaType": "JSON", "schema": json!({ "type": "string" }).to_string() })).await;
+
+    let proto = "syntax = \"proto3\"; import \"address.proto\"; message Employee { Address home = 1; }";
+    let (status, res) = call(&app, "POST", "/subjects/employee/versions", Some(json!({ "schemaType": "PROTOBUF", "schema": proto }))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res["error_code"], json!(42201));
+
+    let references = json!([{ "name": "address.proto", "subject": "address", "version": 1 }]);
+    register(&app, "employee", json!({ "schemaType": "PROTOBUF", "schema": proto, "references": references })).await;
+
+    // the well-known types come with the parser
+    let proto = "syntax = \"proto3\"; import \"google/protobuf/timestamp.proto\"; message Event { google.protobuf.Timestamp at = 1; }";
+    register(&app, "event", json!({ "schemaType": "PROTOBUF", "schema": proto })).await;
+
+    let json_schema = json!({ "type": "object", "properties": { "home": { "$ref": "address.json" }, "work": { "$ref": "address.json#" } } }).to_string();
+    let (status, res) = call(&app, "POST", "/subjects/person/versions", Some(json!({ "schemaType": "JSON", "schema": json_schema }))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res, json!({ "error_code": 42201, "message": "Invalid schema: address.json does not match any reference" }));
+
+    let references = json!([{ "name": "address.json", "subject": "address_json", "version": 1 }]);
+    register(&app, "person", json!({ "schemaType": "JSON", "schema": json_schema, "references": references })).await;
+}
+
+#[tokio::test]
+async fn list_subjects_skips_deleted_subjects() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "person", employee_v1()).await;
+
+    let (status, subjects) = call(&app, "GET", "/subjects", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(subjects, json!(["employee", "person"]));
+
+    call(&app, "DELETE", "/subjects/employee", None).await;
+
+    let (_, subjects) = call(&app, "GET", "/subjects", None).await;
+    assert_eq!(subjects, json!(["person"]));
+}
+
+#[tokio::test]
+async fn list_subjects_and_versions_in_pages() {
+    let app = registry();
+    for subject in ["team", "employee_2", "employee", "person"] {
+        register(&app, subject, employee_v1()).await;
+    }
+    register(&app, "employee", employee_v2()).await;
+
+    let (_, subjects) = call(&app, "GET", "/subjects", None).await;
+    assert_eq!(subjects, json!(["employee", "employee_2", "person", "team"]));
+
+    let (_, subjects) = call(&app, "GET", "/subjects?subjectPrefix=employee", None).await;
+    assert_eq!(subjects, json!(["employee", "employee_2"]));
+
+    let (_, subjects) = call(&app, "GET", "/subjects?offset=1&limit=2", None).await;
+    assert_eq!(subjects, json!(["employee_2", "person"]));
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions?offset=1", None).await;
+    assert_eq!(versions, json!([2]));
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions?limit=1", None).await;
+    assert_eq!(versions, json!([1]));
+}
+
+#[tokio::test]
+async fn get_subject_versions_of_unknown_subject() {
+    let app = registry();
+
+    let (status, res) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40401));
+}
+
+#[tokio::test]
+async fn get_by_version_resolves_latest() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+
+    for version in ["latest", "-1", "2"] {
+        let (status, res) = call(&app, "GET", &format!("/subjects/employee/versions/{}", version), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(res, json!({ "subject": "employee", "version": 2, "id": 2, "schema": employee_v2()["schema"] }));
+    }
+}
+
+#[tokio::test]
+async fn get_by_version_errors() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+
+    let (status, res) = call(&app, "GET", "/subjects/employee/versions/3", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40402));
+
+    let (status, res) = call(&app, "GET", "/subjects/employee/versions/first", None).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res["error_code"], json!(42202));
+
+    let (status, res) = call(&app, "GET", "/subjects/person/versions/1", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40401));
+}
+
+#[tokio::test]
+async fn get_schema_by_version_returns_the_schema_text() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+
+    let (status, res) = call(&app, "GET", "/subjects/employee/versions/1/schema", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res.to_string(), employee_v1()["schema"].as_str().unwrap());
+}
+
+#[tokio::test]
+async fn get_schema_by_id() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+
+    let (status, res) = call(&app, "GET", "/schemas/ids/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({ "schema": employee_v1()["schema"] }));
+
+    let (status, res) = call(&app, "GET", "/schemas/ids/2", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40403));
+}
+
+#[tokio::test]
+async fn schema_subjects_and_versions() {
+    let app = registry();
+    register(&app, "person", employee_v1()).await;
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+    register(&app, "other", employee_v1()).await;
+    call(&app, "DELETE", "/subjects/other", None).await;
+
+    let (status, res) = call(&app, "GET", "/schemas/ids/1/subjects", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!(["employee", "person"]));
+
+    let (_, res) = call(&app, "GET", "/schemas/ids/1/subjects?deleted=true", None).await;
+    assert_eq!(res, json!(["employee", "other", "person"]));
+
+    let (_, res) = call(&app, "GET", "/schemas/ids/1/versions", None).await;
+    assert_eq!(res, json!([{ "subject": "employee", "version": 1 }, { "subject": "person", "version": 1 }]));
+
+    call(&app, "DELETE", "/subjects/employee/versions/2", None).await;
+    let (_, res) = call(&app, "GET", "/schemas/ids/2/versions", None).await;
+    assert_eq!(res, json!([]));
+    let (_, res) = call(&app, "GET", "/schemas/ids/2/versions?deleted=true", None).await;
+    assert_eq!(res, json!([{ "subject": "employee", "version": 2 }]));
+
+    let (status, res) = call(&app, "GET", "/schemas/ids/3/subjects", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40403));
+}
+
+#[tokio::test]
+async fn list_schemas() {
+    let app = registry();
+    register(&app, "person", employee_v1()).await;
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+    register(&app, "other", employee_v1()).await;
+    call(&app, "DELETE", "/subjects/other", None).await;
+
+    let listed = |res: &Value| res.as_array().unwrap().iter().map(|x| format!("{}:{}", x["subject"].as_str().unwrap(), x["version"])).collect::<Vec<_>>();
+
+    let (status, res) = call(&app, "GET", "/schemas", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed(&res), ["employee:1", "employee:2", "person:1"]);
+    assert_eq!(res[1], json!({ "subject": "employee", "version": 2, "id": 2, "schema": employee_v2()["schema"] }));
+
+    let (_, res) = call(&app, "GET", "/schemas?latestOnly=true", None).await;
+    assert_eq!(listed(&res), ["employee:2", "person:1"]);
+
+    let (_, res) = call(&app, "GET", "/schemas?subjectPrefix=emp", None).await;
+    assert_eq!(listed(&res), ["employee:1", "employee:2"]);
+
+    let (_, res) = call(&app, "GET", "/schemas?deleted=true&offset=2&limit=1", None).await;
+    assert_eq!(listed(&res), ["other:1"]);
+
+    let (_, res) = call(&app, "GET", "/schemas/types", None).await;
+    assert_eq!(res, json!(["AVRO", "JSON", "PROTOBUF"]));
+}
+
+#[tokio::test]
+async fn check_schema_existence() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+
+    let (status, res) = call(&app, "POST", "/subjects/employee", Some(employee_v1())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({ "subject": "employee", "version": 1, "id": 1, "schema": employee_v1()["schema"] }));
+
+    let (status, res) = call(&app, "POST", "/subjects/employee", Some(employee_v2())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40403));
+
+    let (status, res) = call(&app, "POST", "/subjects/person", Some(employee_v1())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40401));
+}
+
+#[tokio::test]
+async fn delete_by_version_soft_deletes_the_version() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+
+    let (status, res) = call(&app, "DELETE", "/subjects/employee/versions/2", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!(1));
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1]));
+
+    let (status, _) = call(&app, "GET", "/subjects/employee/versions/2", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // the id comes back when the same schema is registered again
+    assert_eq!(register(&app, "employee", employee_v2()).await, json!({ "id": 2 }));
+}
+
+#[tokio::test]
+async fn delete_by_version_keeps_schemas_used_by_other_subjects() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "person", employee_v1()).await;
+
+    call(&app, "DELETE", "/subjects/employee/versions/1", None).await;
+
+    let (status, _) = call(&app, "GET", "/subjects/person/versions/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn permanent_delete_by_version_requires_a_soft_delete() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+
+    let (status, res) = call(&app, "DELETE", "/subjects/employee/versions/2?permanent=true", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res, json!({ "error_code": 40407, "message": "Subject 'employee' Version 2 was not deleted first before being permanently deleted" }));
+
+    call(&app, "DELETE", "/subjects/employee/versions/2", None).await;
+    let (status, _) = call(&app, "GET", "/schemas/ids/2", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, res) = call(&app, "DELETE", "/subjects/employee/versions/2?permanent=true", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!(1));
+
+    let (status, _) = call(&app, "GET", "/schemas/ids/2", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, res) = call(&app, "DELETE", "/subjects/employee/versions/2?permanent=true", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40402));
+}
+
+#[tokio::test]
+async fn permanent_delete_keeps_schemas_used_by_other_subjects() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "person", employee_v1()).await;
+
+    call(&app, "DELETE", "/subjects/employee/versions/1", None).await;
+    call(&app, "DELETE", "/subjects/employee/versions/1?permanent=true", None).await;
+
+    let (status, res) = call(&app, "GET", "/subjects/person/versions/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res["id"], json!(1));
+
+    call(&app, "DELETE", "/subjects/person", None).await;
+    register(&app, "other", employee_v1()).await;
+    call(&app, "DELETE", "/subjects/other", None).await;
+
+    // a soft deleted version of another subject keeps the schema too
+    call(&app, "DELETE", "/subjects/person?permanent=true", None).await;
+    let (status, _) = call(&app, "GET", "/schemas/ids/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    call(&app, "DELETE", "/subjects/other?permanent=true", None).await;
+    let (status, _) = call(&app, "GET", "/schemas/ids/1", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn permanent_delete_subject_requires_a_soft_delete() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+    call(&app, "PUT", "/config/employee", Some(json!({ "compatibility": "NONE" }))).await;
+
+    let (status, res) = call(&app, "DELETE", "/subjects/employee?permanent=true", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res, json!({ "error_code": 40405, "message": "Subject 'employee' was not deleted first before being permanently deleted" }));
+
+    call(&app, "DELETE", "/subjects/employee", None).await;
+    let (status, res) = call(&app, "DELETE", "/subjects/employee?permanent=true", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!([1, 2]));
+
+    let (status, res) = call(&app, "DELETE", "/subjects/employee?permanent=true", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40401));
+
+    let (status, _) = call(&app, "GET", "/schemas/ids/1", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // the subject starts over without its config
+    register(&app, "employee", employee_v2()).await;
+    let (status, _) = call(&app, "POST", "/subjects/employee/versions", Some(employee_incompatible())).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn deleted_lookups_include_soft_deleted_data() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+    register(&app, "person", employee(json!([]))).await;
+    call(&app, "DELETE", "/subjects/employee/versions/2", None).await;
+    call(&app, "DELETE", "/subjects/person", None).await;
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1]));
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions?deleted=true", None).await;
+    assert_eq!(versions, json!([1, 2]));
+
+    let (status, _) = call(&app, "GET", "/subjects/employee/versions/2", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, res) = call(&app, "GET", "/subjects/employee/versions/2?deleted=true", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res["id"], json!(2));
+
+    let (_, res) = call(&app, "GET", "/subjects/employee/versions/latest?deleted=true", None).await;
+    assert_eq!(res["version"], json!(2));
+    let (status, _) = call(&app, "GET", "/subjects/employee/versions/2/schema?deleted=true", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, subjects) = call(&app, "GET", "/subjects", None).await;
+    assert_eq!(subjects, json!(["employee"]));
+    let (_, subjects) = call(&app, "GET", "/subjects?deleted=true", None).await;
+    assert_eq!(subjects, json!(["employee", "person"]));
+
+    let (status, _) = call(&app, "GET", "/subjects/person/versions", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, versions) = call(&app, "GET", "/subjects/person/versions?deleted=true", None).await;
+    assert_eq!(versions, json!([1]));
+    let (_, res) = call(&app, "GET", "/subjects/person/versions/1?deleted=true", None).await;
+    assert_eq!(res["id"], json!(3));
+}
+
+#[tokio::test]
+async fn undelete_restores_the_version_numbers() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+
+    call(&app, "DELETE", "/subjects/employee/versions/1", None).await;
+    let (status, res) = call(&app, "POST", "/subjects/employee/versions/1/undelete", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!(1));
+
+    let (_, res) = call(&app, "GET", "/subjects/employee/versions/1", None).await;
+    assert_eq!(res["id"], json!(1));
+
+    call(&app, "DELETE", "/subjects/employee", None).await;
+    let (status, res) = call(&app, "POST", "/subjects/employee/undelete", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!([1, 2]));
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1, 2]));
+    let (_, res) = call(&app, "GET", "/subjects/employee/versions/latest", None).await;
+    assert_eq!(res["id"], json!(2));
+
+    let (status, res) = call(&app, "POST", "/subjects/employee/versions/3/undelete", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40402));
+
+    let (status, res) = call(&app, "POST", "/subjects/person/undelete", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40401));
+}
+
+#[tokio::test]
+async fn registration_skips_soft_deleted_version_numbers() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+
+    call(&app, "DELETE", "/subjects/employee/versions/2", None).await;
+    call(&app, "PUT", "/config/employee", Some(json!({ "compatibility": "NONE" }))).await;
+    register(&app, "employee", employee_incompatible()).await;
+
+    let (_, res) = call(&app, "GET", "/subjects/employee/versions/latest", None).await;
+    assert_eq!(res["version"], json!(3));
+    assert_eq!(res["id"], json!(3));
+
+    let (_, res) = call(&app, "POST", "/subjects/employee/versions/2/undelete", None).await;
+    assert_eq!(res, json!(1));
+
+    let (_, res) = call(&app, "GET", "/subjects/employee/versions/2", None).await;
+    assert_eq!(res["id"], json!(2));
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1, 2, 3]));
+}
+
+#[tokio::test]
+async fn delete_subject_returns_the_schema_ids() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+
+    let (status, res) = call(&app, "DELETE", "/subjects/employee", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!([1, 2]));
+
+    let (status, res) = call(&app, "DELETE", "/subjects/employee", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40401));
+
+    // registering again revives the subject after the numbers of its soft deleted versions
+    register(&app, "employee", employee_v1()).await;
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([3]));
+}
+
+#[tokio::test]
+async fn references_are_resolved_and_protect_their_target() {
+    let app = registry();
+
+    let address = json!({ "type": "record", "name": "Address", "namespace": "test", "fields": [{ "name": "street", "type": "string" }] });
+    register(&app, "address", json!({ "schema": address.to_string() })).await;
+
+    let references = json!([{ "name": "test.Address", "subject": "address", "version": 1 }]);
+    let employee = json!({ "type": "record", "name": "Employee", "namespace": "test", "fields": [{ "name": "home", "type": "Address" }, { "name": "work", "type": ["null", "Address"] }] });
+    let body = json!({ "schema": employee.to_string(), "references": references });
+
+    assert_eq!(register(&app, "employee", body.clone()).await, json!({ "id": 2 }));
+
+    let (status, res) = call(&app, "POST", "/subjects/employee/versions", Some(json!({ "schema": employee.to_string() }))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res["error_code"], json!(42201));
+
+    let (_, res) = call(&app, "GET", "/schemas/ids/2", None).await;
+    assert_eq!(res, json!({ "schema": employee.to_string(), "references": references }));
+
+    let (_, res) = call(&app, "GET", "/subjects/employee/versions/1", None).await;
+    assert_eq!(res, json!({ "subject": "employee", "version": 1, "id": 2, "references": references, "schema": employee.to_string() }));
+
+    let (status, res) = call(&app, "GET", "/subjects/address/versions/1/referencedby", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!([2]));
+
+    let (status, res) = call(&app, "DELETE", "/subjects/address/versions/1", None).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res["error_code"], json!(42206));
+
+    let (status, _) = call(&app, "DELETE", "/subjects/address", None).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (_, res) = call(&app, "POST", "/compatibility/subjects/employee/versions/1", Some(body)).await;
+    assert_eq!(res, json!({ "compatibility": "FULL" }));
+
+    call(&app, "DELETE", "/subjects/employee", None).await;
+
+    let (_, res) = call(&app, "GET", "/subjects/address/versions/1/referencedby", None).await;
+    assert_eq!(res, json!([]));
+
+    let (status, _) = call(&app, "DELETE", "/subjects/address/versions/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn check_compatibility_with_a_version() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+
+    let (status, res) = call(&app, "POST", "/compatibility/subjects/employee/versions/1", Some(employee_v2())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({ "compatibility": "FULL" }));
+
+    let (_, res) = call(&app, "POST", "/compatibility/subjects/employee/versions/latest", Some(employee(json!([{ "name": "name", "type": "string" }, { "name": "age", "type": "int" }])))).await;
+    assert_eq!(res, json!({ "compatibility": "FORWARD" }));
+
+    let (_, res) = call(&app, "POST", "/compatibility/subjects/employee/versions/1?verbose=true", Some(employee_incompatible())).await;
+    assert_eq!(res["compatibility"], json!("NONE"));
+    assert_eq!(res["messages"].as_array().unwrap().len(), 2);
+
+    let (status, res) = call(&app, "POST", "/compatibility/subjects/employee/versions/2", Some(employee_v2())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40402));
+}
+
+#[tokio::test]
+async fn logical_types_are_compatible_with_the_type_they_annotate() {
+    let app = registry();
+    register(&app, "employee", employee(json!([{ "name": "hired", "type": "int" }]))).await;
+
+    let (_, res) = call(&app, "POST", "/compatibility/subjects/employee/versions/1", Some(employee(json!([{ "name": "hired", "type": { "type": "int", "logicalType": "date" } }])))).await;
+    assert_eq!(res, json!({ "compatibility": "FULL" }));
+
+    let (_, res) = call(&app, "POST", "/compatibility/subjects/employee/versions/1", Some(employee(json!([{ "name": "hired", "type": { "type": "long", "logicalType": "timestamp-millis" } }])))).await;
+    assert_eq!(res, json!({ "compatibility": "BACKWARD" }));
+
+    let (_, res) = call(&app, "POST", "/compatibility/subjects/employee/versions/1", Some(employee(json!([{ "name": "hired", "type": { "type": "string", "logicalType": "uuid" } }])))).await;
+    assert_eq!(res, json!({ "compatibility": "NONE" }));
+}
+
+#[tokio::test]
+async fn check_subject_compatibility_uses_the_subject_compatibility() {
+    let app = registry();
+
+    let (_, res) = call(&app, "POST", "/compatibility/subjects/employee/versions", Some(employee_v1())).await;
+    assert_eq!(res, json!({ "is_compatible": true, "messages": [] }));
+
+    register(&app, "employee", employee_v1()).await;
+    call(&app, "PUT", "/config/employee", Some(json!({ "compatibility": "NONE" }))).await;
+    register(&app, "employee", employee_incompatible()).await;
+
+    let (_, res) = call(&app, "POST", "/compatibility/subjects/employee/versions", Some(employee_incompatible())).await;
+    assert_eq!(res, json!({ "is_compatible": true, "messages": [] }));
+
+    call(&app, "PUT", "/config/employee", Some(json!({ "compatibility": "BACKWARD_TRANSITIVE" }))).await;
+
+    let (status, res) = call(&app, "POST", "/compatibility/subjects/employee/versions", Some(employee_incompatible())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res["is_compatible"], json!(false));
+    assert_eq!(res["messages"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn global_config() {
+    let app = registry();
+
+    let (status, res) = call(&app, "GET", "/config", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({ "compatibilityLevel": "BACKWARD" }));
+
+    let (status, res) = call(&app, "PUT", "/config", Some(json!({ "compatibility": "FULL" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({ "compatibility": "FULL" }));
+
+    call(&app, "PUT", "/config", Some(json!({ "compatibility": "FORWARD" }))).await;
+    let (_, res) = call(&app, "GET", "/config", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "FORWARD" }));
+
+    let (status, res) = call(&app, "PUT", "/config", Some(json!({ "compatibility": "SOMETIMES" }))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res["error_code"], json!(42203));
+
+    let (status, res) = call(&app, "DELETE", "/config", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({ "compatibilityLevel": "FORWARD" }));
+
+    let (_, res) = call(&app, "GET", "/config", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "BACKWARD" }));
+}
+
+#[tokio::test]
+async fn subject_config() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+
+    let (status, res) = call(&app, "GET", "/config/employee", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40408));
+
+    call(&app, "PUT", "/config", Some(json!({ "compatibility": "FULL" }))).await;
+    let (status, res) = call(&app, "GET", "/config/employee?defaultToGlobal=true", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({ "compatibilityLevel": "FULL" }));
+
+    let (status, res) = call(&app, "PUT", "/config/employee", Some(json!({ "compatibility": "NONE" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({ "compatibility": "NONE" }));
+
+    let (_, res) = call(&app, "GET", "/config/employee", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "NONE" }));
+
+    register(&app, "employee", employee_incompatible()).await;
+
+    let (status, res) = call(&app, "DELETE", "/config/employee", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({ "compatibilityLevel": "NONE" }));
+
+    let (status, res) = call(&app, "DELETE", "/config/employee", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40408));
+
+    // the subject config is gone, the global one is left alone
+    let (_, res) = call(&app, "GET", "/config", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "FULL" }));
+}
+
+#[tokio::test]
+async fn config_fields() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+
+    let metadata = json!({ "properties": { "owner": "payroll" } });
+    let rule_set = json!({ "domainRules": [{ "name": "checkAge", "kind": "CONDITION", "type": "CEL", "mode": "WRITE", "expr": "message.age > 0" }] });
+
+    let (status, res) = call(&app, "PUT", "/config", Some(json!({ "compatibility": "full", "normalize": true, "defaultMetadata": metadata }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({ "compatibility": "FULL", "normalize": true, "defaultMetadata": metadata }));
+
+    // the fields left out keep their value
+    call(&app, "PUT", "/config", Some(json!({ "compatibilityGroup": "application.version" }))).await;
+    let (_, res) = call(&app, "GET", "/config", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "FULL", "normalize": true, "compatibilityGroup": "application.version", "defaultMetadata": metadata }));
+
+    call(&app, "PUT", "/config/employee", Some(json!({ "compatibilityLevel": "NONE", "compatibilityGroup": "major", "overrideRuleSet": rule_set }))).await;
+    let (_, res) = call(&app, "GET", "/config/employee", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "NONE", "compatibilityGroup": "major", "overrideRuleSet": rule_set }));
+
+    // the subject's level is used over the global one
+    register(&app, "employee", employee_incompatible()).await;
+    let (_, res) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(res, json!([1, 2]));
+
+    call(&app, "DELETE", "/config/employee", None).await;
+    let (_, res) = call(&app, "GET", "/config/employee?defaultToGlobal=true", None).await;
+    assert_eq!(res["compatibilityLevel"], json!("FULL"));
+
+    let (_, res) = call(&app, "DELETE", "/config", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "FULL", "normalize": true, "compatibilityGroup": "application.version", "defaultMetadata": metadata }));
+    let (_, res) = call(&app, "GET", "/config", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "BACKWARD" }));
+}
+
+#[tokio::test]
+async fn subject_alias() {
+    let app = registry();
+    register(&app, "staff", employee(json!([]))).await;
+    register(&app, "employee", employee_v1()).await;
+
+    let (status, _) = call(&app, "PUT", "/config/staff", Some(json!({ "alias": "employee" }))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // reads and registrations through the alias go to the subject it points to
+    let (_, res) = call(&app, "GET", "/subjects/staff/versions/latest", None).await;
+    assert_eq!(res["subject"], json!("employee"));
+    assert_eq!(res["id"], json!(2));
+
+    assert_eq!(register(&app, "staff", employee_v2()).await, json!({ "id": 3 }));
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1, 2]));
+    let (_, versions) = call(&app, "GET", "/subjects/staff/versions", None).await;
+    assert_eq!(versions, json!([1, 2]));
+
+    let (status, res) = call(&app, "POST", "/subjects/staff", Some(employee_v1())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res["version"], json!(1));
+
+    // deletes would hit the alias subject's own versions rather than the ones it reads, so they are rejected
+    for (method, uri) in [("DELETE", "/subjects/staff/versions/1"), ("DELETE", "/subjects/staff"), ("POST", "/subjects/staff/versions/1/undelete"), ("POST", "/subjects/staff/undelete")] {
+        let (status, res) = call(&app, method, uri, None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{} {}", method, uri);
+        assert_eq!(res["error_code"], json!(42205));
+    }
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1, 2]));
+
+    // the alias keeps its own config, and reads its own versions again once the alias is removed
+    let (_, res) = call(&app, "GET", "/config/staff", None).await;
+    assert_eq!(res, json!({ "alias": "employee" }));
+
+    call(&app, "DELETE", "/config/staff", None).await;
+    let (_, res) = call(&app, "GET", "/subjects/staff/versions/latest", None).await;
+    assert_eq!(res["subject"], json!("staff"));
+    assert_eq!(res["id"], json!(1));
+
+    let (status, _) = call(&app, "DELETE", "/subjects/staff/versions/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // a global alias is not passed down to the subjects
+    call(&app, "PUT", "/config", Some(json!({ "alias": "employee" }))).await;
+    let (_, res) = call(&app, "GET", "/config/staff?defaultToGlobal=true", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "BACKWARD" }));
+    let (_, res) = call(&app, "GET", "/subjects/staff/versions?deleted=true", None).await;
+    assert_eq!(res, json!([1]));
+}
+
+#[tokio::test]
+async fn subject_alias_without_versions() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+
+    // a name can be an alias before anything is registered under it
+    let (status, _) = call(&app, "PUT", "/config/staff", Some(json!({ "alias": "employee" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, res) = call(&app, "GET", "/config/staff", None).await;
+    assert_eq!(res, json!({ "alias": "employee" }));
+
+    let (_, res) = call(&app, "GET", "/subjects/staff/versions/latest", None).await;
+    assert_eq!(res["subject"], json!("employee"));
+    assert_eq!(register(&app, "staff", employee_v2()).await, json!({ "id": 2 }));
+
+    let (_, subjects) = call(&app, "GET", "/subjects", None).await;
+    assert_eq!(subjects, json!(["employee"]));
+
+    // and so can a soft deleted subject
+    register(&app, "person", employee(json!([]))).await;
+    call(&app, "DELETE", "/subjects/person", None).await;
+    let (status, _) = call(&app, "PUT", "/config/person", Some(json!({ "alias": "employee" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, versions) = call(&app, "GET", "/subjects/person/versions", None).await;
+    assert_eq!(versions, json!([1, 2]));
+
+    // other configs still need a live subject
+    let (status, _) = call(&app, "PUT", "/config/manager", Some(json!({ "alias": "employee", "compatibility": "NONE" }))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn subject_config_of_unknown_subject() {
+    let app = registry();
+
+    let (status, res) = call(&app, "PUT", "/config/person", Some(json!({ "compatibility": "NONE" }))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40401));
+
+    let (_, res) = call(&app, "GET", "/config", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "BACKWARD" }));
+
+    let (status, res) = call(&app, "GET", "/config/person", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40401));
+}
+
+#[tokio::test]
+async fn global_mode() {
+    let app = registry();
+
+    let (status, res) = call(&app, "GET", "/mode", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({ "mode": "READWRITE" }));
+
+    let (status, res) = call(&app, "PUT", "/mode", Some(json!({ "mode": "READONLY" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({ "mode": "READONLY" }));
+
+    let (_, res) = call(&app, "GET", "/mode", None).await;
+    assert_eq!(res, json!({ "mode": "READONLY" }));
+
+    let (status, res) = call(&app, "PUT", "/mode", Some(json!({ "mode": "WRITEONLY" }))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res["error_code"], json!(42204));
+}
+
+#[tokio::test]
+async fn subject_mode() {
+    let app = registry();
+
+    let (status, res) = call(&app, "PUT", "/mode/employee", Some(json!({ "mode": "READONLY" }))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(40401));
+
+    register(&app, "employee", employee_v1()).await;
+    call(&app, "PUT", "/mode", Some(json!({ "mode": "IMPORT" }))).await;
+
+    // the global mode only with ?defaultToGlobal=true
+    let (status, res) = call(&app, "GET", "/mode/employee", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res, json!({ "error_code": 40409, "message": "Subject 'employee' does not have subject-level mode configured" }));
+    let (_, res) = call(&app, "GET", "/mode/employee?defaultToGlobal=true", None).await;
+    assert_eq!(res, json!({ "mode": "IMPORT" }));
+
+    call(&app, "PUT", "/mode/employee", Some(json!({ "mode": "READWRITE" }))).await;
+    let (_, res) = call(&app, "GET", "/mode/employee", None).await;
+    assert_eq!(res, json!({ "mode": "READWRITE" }));
+}
+
+#[tokio::test]
+async fn read_only_mode_rejects_writes() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "person", employee_v1()).await;
+    call(&app, "PUT", "/mode/employee", Some(json!({ "mode": "READONLY" }))).await;
+
+    let (status, res) = call(&app, "POST", "/subjects/employee/versions", Some(employee_v2())).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res, json!({ "error_code": 42205, "message": "Subject employee is in read-only mode" }));
+
+    let (status, _) = call(&app, "DELETE", "/subjects/employee/versions/1", None).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, _) = call(&app, "DELETE", "/subjects/employee", None).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, _) = call(&app, "GET", "/subjects/employee/versions/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    register(&app, "person", employee_v2()).await;
+}
+
+#[tokio::test]
+async fn import_mode_keeps_explicit_ids() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+
+    let mut body = employee_v2();
+    body["id"] = json!(7);
+    body["version"] = json!(5);
+
+    let (status, res) = call(&app, "POST", "/subjects/employee/versions", Some(body.clone())).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res, json!({ "error_code": 42205, "message": "Subject employee is not in import mode" }));
+
+    call(&app, "PUT", "/mode", Some(json!({ "mode": "IMPORT" }))).await;
+    assert_eq!(register(&app, "employee", body).await, json!({ "id": 7 }));
+
+    let (_, res) = call(&app, "GET", "/subjects/employee/versions/latest", None).await;
+    assert_eq!(res["version"], json!(5));
+    assert_eq!(res["id"], json!(7));
+
+    // without a version the schema becomes the next one, compatible or not
+    let mut body = employee_incompatible();
+    body["id"] = json!(3);
+    assert_eq!(register(&app, "employee", body).await, json!({ "id": 3 }));
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1, 5, 6]));
+
+    // a schema the subject already has keeps its id, another one is not taken
+    let mut body = employee_v1();
+    body["id"] = json!(1);
+    assert_eq!(register(&app, "employee", body.clone()).await, json!({ "id": 1 }));
+    body["id"] = json!(9);
+    let (status, res) = call(&app, "POST", "/subjects/employee/versions", Some(body)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res, json!({ "error_code": 42207, "message": "Schema already registered with id 1 instead of input id 9" }));
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1, 5, 6]));
+
+    let mut body = employee(json!([]));
+    body["id"] = json!(7);
+    let (status, res) = call(&app, "POST", "/subjects/person/versions", Some(body)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res["error_code"], json!(42205));
+
+    call(&app, "PUT", "/mode", Some(json!({ "mode": "READWRITE" }))).await;
+    assert_eq!(register(&app, "person", employee(json!([]))).await, json!({ "id": 8 }));
+}
+
+async fn media_type(app: &Router, accept: Option<&str>) -> (StatusCode, Option<String>) {
+    let mut request = Request::builder().uri("/subjects");
+    if let Some(accept) = accept {
+        request = request.header("Accept", accept);
+    }
+
+    let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+    let content_type = response.headers().get("Content-Type").map(|x| x.to_str().unwrap().to_string());
+
+    (response.status(), content_type)
+}
+
+#[tokio::test]
+async fn content_type_negotiation() {
+    let app = registry();
+    let v1 = Some(String::from("application/vnd.schemaregistry.v1+json"));
+
+    assert_eq!(media_type(&app, None).await, (StatusCode::OK, v1.clone()));
+    assert_eq!(media_type(&app, Some("application/vnd.schemaregistry+json")).await, (StatusCode::OK, v1.clone()));
+    assert_eq!(media_type(&app, Some("*/*")).await, (StatusCode::OK, v1.clone()));
+    assert_eq!(media_type(&app, Some("application/json")).await, (StatusCode::OK, Some(String::from("application/json"))));
+    assert_eq!(media_type(&app, Some("application/json; q=0.5, application/vnd.schemaregistry.v1+json")).await, (StatusCode::OK, v1));
+    assert_eq!(media_type(&app, Some("text/html")).await.0, StatusCode::NOT_ACCEPTABLE);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/subjects/employee/versions")
+        .header("Content-Type", "application/vnd.schemaregistry.v1+json")
+        .body(Body::from(employee_v1().to_string()))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn unknown_routes() {
+    let app = registry();
+
+    let (status, res) = call(&app, "GET", "/unknown", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(res["error_code"], json!(404));
+}
+
+#[tokio::test]
+async fn sqlite_registers_and_soft_deletes() {
+    let app = sqlite_registry().await;
+
+    assert_eq!(register(&app, "employee", employee_v1()).await, json!({ "id": 1 }));
+    assert_eq!(register(&app, "employee", employee_v1()).await, json!({ "id": 1 }));
+    assert_eq!(register(&app, "person", employee_v1()).await, json!({ "id": 1 }));
+    // like postgres sequences, upserts of existing schemas use up ids
+    let id = register(&app, "employee", employee_v2()).await["id"].clone();
+    assert_ne!(id, json!(1));
+
+    let (status, _) = call(&app, "POST", "/subjects/employee/versions", Some(employee_incompatible())).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (_, res) = call(&app, "GET", "/subjects/employee/versions/latest", None).await;
+    assert_eq!(res["version"], json!(2));
+    assert_eq!(res["id"], id);
+
+    call(&app, "DELETE", "/subjects/employee/versions/2", None).await;
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1]));
+
+    let (_, res) = call(&app, "DELETE", "/subjects/employee", None).await;
+    assert_eq!(res, json!([1]));
+
+    let (_, subjects) = call(&app, "GET", "/subjects", None).await;
+    assert_eq!(subjects, json!(["person"]));
+
+    let (status, _) = call(&app, "GET", "/subjects/person/versions/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    register(&app, "employee", employee_v2()).await;
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([3]));
+}
+
+#[tokio::test]
+async fn sqlite_references_and_config() {
+    let app = sqlite_registry().await;
+
+    let address = json!({ "type": "record", "name": "Address", "namespace": "test", "fields": [{ "name": "street", "type": "string" }] });
+    register(&app, "address", json!({ "schema": address.to_string() })).await;
+
+    let references = json!([{ "name": "test.Address", "subject": "address", "version": 1 }]);
+    let employee = json!({ "type": "record", "name": "Employee", "namespace": "test", "fields": [{ "name": "home", "type": "Address" }] });
+    let id = register(&app, "employee", json!({ "schema": employee.to_string(), "references": references })).await["id"].clone();
+
+    let (_, res) = call(&app, "GET", &format!("/schemas/ids/{}", id), None).await;
+    assert_eq!(res["references"], references);
+
+    let (_, res) = call(&app, "GET", "/subjects/address/versions/1/referencedby", None).await;
+    assert_eq!(res, json!([id]));
+
+    let (status, _) = call(&app, "DELETE", "/subjects/address", None).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    call(&app, "PUT", "/config/employee", Some(json!({ "compatibility": "FULL_TRANSITIVE" }))).await;
+    let (_, res) = call(&app, "GET", "/config/employee", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "FULL_TRANSITIVE" }));
+
+    call(&app, "PUT", "/config", Some(json!({ "compatibility": "FULL" }))).await;
+    call(&app, "PUT", "/config", Some(json!({ "compatibility": "NONE" }))).await;
+    let (_, res) = call(&app, "GET", "/config", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "NONE" }));
+
+    let (_, res) = call(&app, "DELETE", "/config/employee", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "FULL_TRANSITIVE" }));
+    let (_, res) = call(&app, "GET", "/config/employee?defaultToGlobal=true", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "NONE" }));
+
+    call(&app, "DELETE", "/config", None).await;
+    let (_, res) = call(&app, "GET", "/config", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "BACKWARD" }));
+
+    let metadata = json!({ "tags": { "age": ["PII"] }, "sensitive": ["age"] });
+    call(&app, "PUT", "/config/employee", Some(json!({ "normalize": false, "alias": "person", "overrideMetadata": metadata, "defaultRuleSet": { "migrationRules": [{ "name": "upgrade" }] } }))).await;
+    let (_, res) = call(&app, "GET", "/config/employee", None).await;
+    assert_eq!(res, json!({ "normalize": false, "alias": "person", "overrideMetadata": metadata, "defaultRuleSet": { "migrationRules": [{ "name": "upgrade" }] } }));
+}
+
+#[tokio::test]
+async fn sqlite_modes() {
+    let app = sqlite_registry().await;
+    register(&app, "employee", employee_v1()).await;
+
+    call(&app, "PUT", "/mode", Some(json!({ "mode": "IMPORT" }))).await;
+    call(&app, "PUT", "/mode", Some(json!({ "mode": "READONLY" }))).await;
+    let (_, res) = call(&app, "GET", "/mode", None).await;
+    assert_eq!(res, json!({ "mode": "READONLY" }));
+
+    call(&app, "PUT", "/mode/employee", Some(json!({ "mode": "IMPORT" }))).await;
+    let mut body = employee_v2();
+    body["id"] = json!(20);
+    assert_eq!(register(&app, "employee", body).await, json!({ "id": 20 }));
+
+    let (_, res) = call(&app, "GET", "/subjects/employee/versions/2", None).await;
+    assert_eq!(res["id"], json!(20));
+}
+
+#[tokio::test]
+async fn sqlite_list_subjects_and_versions_in_pages() {
+    let app = sqlite_registry().await;
+    for subject in ["team", "employee_2", "employee", "person"] {
+        register(&app, subject, employee_v1()).await;
+    }
+    register(&app, "employee", employee_v2()).await;
+
+    let (_, subjects) = call(&app, "GET", "/subjects?subjectPrefix=employee", None).await;
+    assert_eq!(subjects, json!(["employee", "employee_2"]));
+
+    let (_, subjects) = call(&app, "GET", "/subjects?offset=1&limit=2", None).await;
+    assert_eq!(subjects, json!(["employee_2", "person"]));
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions?offset=1&limit=5", None).await;
+    assert_eq!(versions, json!([2]));
+}
+
+#[tokio::test]
+async fn sqlite_list_schemas() {
+    let app = sqlite_registry().await;
+    register(&app, "person", employee_v1()).await;
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+    register(&app, "employee_2", employee_v1()).await;
+    call(&app, "DELETE", "/subjects/employee/versions/2", None).await;
+
+    let listed = |res: &Value| res.as_array().unwrap().iter().map(|x| format!("{}:{}", x["subject"].as_str().unwrap(), x["version"])).collect::<Vec<_>>();
+
+    let (_, res) = call(&app, "GET", "/schemas?latestOnly=true", None).await;
+    assert_eq!(listed(&res), ["employee:1", "employee_2:1", "person:1"]);
+
+    let (_, res) = call(&app, "GET", "/schemas?latestOnly=true&deleted=true&subjectPrefix=employee", None).await;
+    assert_eq!(listed(&res), ["employee:2", "employee_2:1"]);
+
+    let (_, res) = call(&app, "GET", "/schemas?offset=1&limit=2", None).await;
+    assert_eq!(listed(&res), ["employee_2:1", "person:1"]);
+}
+
+#[tokio::test]
+async fn sqlite_permanent_deletes() {
+    let app = sqlite_registry().await;
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+    register(&app, "person", employee_v1()).await;
+
+    let (status, _) = call(&app, "DELETE", "/subjects/employee/versions/1?permanent=true", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    call(&app, "DELETE", "/subjects/employee", None).await;
+    let (status, res) = call(&app, "DELETE", "/subjects/employee?permanent=true", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res.as_array().unwrap().len(), 2);
+
+    let (status, _) = call(&app, "GET", "/schemas/ids/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn sqlite_deleted_lookups_and_undelete() {
+    let app = sqlite_registry().await;
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+    call(&app, "DELETE", "/subjects/employee", None).await;
+
+    let (_, subjects) = call(&app, "GET", "/subjects?deleted=true", None).await;
+    assert_eq!(subjects, json!(["employee"]));
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions?deleted=true", None).await;
+    assert_eq!(versions, json!([1, 2]));
+    let (status, _) = call(&app, "GET", "/subjects/employee/versions/2?deleted=true", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = call(&app, "POST", "/subjects/employee/versions/2/undelete", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([2]));
+
+    call(&app, "POST", "/subjects/employee/undelete", None).await;
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1, 2]));
+
+    register(&app, "person", employee_v1()).await;
+    let (_, res) = call(&app, "GET", "/schemas/ids/1/subjects", None).await;
+    assert_eq!(res, json!(["employee", "person"]));
+    let (_, res) = call(&app, "GET", "/schemas/ids/1/versions", None).await;
+    assert_eq!(res, json!([{ "subject": "employee", "version": 1 }, { "subject": "person", "version": 1 }]));
+}
+
+#[tokio::test]
+async fn log_replays_on_open() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("schemas.log");
+
+    let app = log_registry(LogRepository::open(&path).await.unwrap());
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+    register(&app, "person", employee_v1()).await;
+    call(&app, "PUT", "/config/person", Some(json!({ "compatibility": "NONE" }))).await;
+    call(&app, "DELETE", "/subjects/employee/versions/2", None).await;
+    call(&app, "DELETE", "/subjects/person", None).await;
+    call(&app, "PUT", "/mode/employee", Some(json!({ "mode": "IMPORT" }))).await;
+    call(&app, "PUT", "/config", Some(json!({ "compatibility": "FULL" }))).await;
+    call(&app, "PUT", "/config/employee", Some(json!({ "compatibility": "FORWARD" }))).await;
+    call(&app, "DELETE", "/config/employee", None).await;
+
+    let app = log_registry(LogRepository::open(&path).await.unwrap());
+
+    let (_, res) = call(&app, "GET", "/config", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "FULL" }));
+    let (status, _) = call(&app, "GET", "/config/employee", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, subjects) = call(&app, "GET", "/subjects", None).await;
+    assert_eq!(subjects, json!(["employee"]));
+
+    let (_, res) = call(&app, "GET", "/mode/employee", None).await;
+    assert_eq!(res, json!({ "mode": "IMPORT" }));
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1]));
+
+    let (status, _) = call(&app, "GET", "/schemas/ids/2", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // configs logged before the other config fields existed name the level `compatibility`
+    let mut content = std::fs::read(&path).unwrap();
+    content.extend_from_slice(b"{\"type\":\"CONFIG\",\"subject\":null,\"compatibility\":\"FORWARD\"}\n");
+    std::fs::write(&path, content).unwrap();
+
+    let app = log_registry(LogRepository::open(&path).await.unwrap());
+    let (_, res) = call(&app, "GET", "/config", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "FORWARD" }));
+
+    // the subject comes back with its config, and new schemas do not reuse ids
+    register(&app, "person", employee_incompatible()).await;
+    register(&app, "person", employee_v1()).await;
+    assert_eq!(register(&app, "person", employee(json!([]))).await, json!({ "id": 4 }));
+}
+
+#[tokio::test]
+async fn schemas_of_an_older_release_are_fingerprinted_again() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("schemas.log");
+
+    let app = log_registry(LogRepository::open(&path).await.unwrap());
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "broken", json!({ "schema": "\"int\"" })).await;
+
+    // the schema as an older release logged it, under a fingerprint of another normalized form, and one that no longer parses
+    let content: Vec<String> = std::fs::read_to_string(&path).unwrap().lines().map(|line| {
+        let mut change: Value = serde_json::from_str(line).unwrap();
+        if change["type"] == json!("SCHEMA") && change["id"] == json!(1) {
+            change["fingerprint"] = json!("0ld");
+            change.as_object_mut().unwrap().remove("lookup_fingerprint");
+        }
+        if change["type"] == json!("SCHEMA") && change["id"] == json!(2) {
+            change["schema"] = json!("{");
+        }
+        change.to_string() + "\n"
+    }).collect();
+    std::fs::write(&path, content.concat()).unwrap();
+
+    let service = Service { repository: LogRepository::open(&path).await.unwrap(), providers: SchemaProviders::default() };
+    let summary = service.schema_rehash().await.unwrap();
+    assert_eq!((summary.updated, summary.unparsed), (1, vec![2]));
+    assert_eq!(service.schema_rehash().await.unwrap().updated, 0);
+
+    let app = log_registry(LogRepository::open(&path).await.unwrap());
+    assert_eq!(register(&app, "person", employee_v1()).await, json!({ "id": 1 }));
+
+    let documented = employee(json!([{ "name": "name", "type": "string", "doc": "the full name" }]));
+    let (status, res) = call(&app, "POST", "/subjects/employee?normalize=true", Some(documented)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res["id"], json!(1));
+}
+
+#[tokio::test]
+async fn log_replays_permanent_deletes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("schemas.log");
+
+    let app = log_registry(LogRepository::open(&path).await.unwrap());
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+    call(&app, "DELETE", "/subjects/employee/versions/2", None).await;
+    call(&app, "DELETE", "/subjects/employee/versions/2?permanent=true", None).await;
+
+    let repository = LogRepository::open(&path).await.unwrap();
+    let app = log_registry(repository.clone());
+    let (status, _) = call(&app, "GET", "/schemas/ids/2", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    call(&app, "DELETE", "/subjects/employee", None).await;
+    call(&app, "DELETE", "/subjects/employee?permanent=true", None).await;
+    repository.compact().await.unwrap();
+
+    let app = log_registry(LogRepository::open(&path).await.unwrap());
+    let (status, _) = call(&app, "GET", "/schemas/ids/1", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(register(&app, "employee", employee_v2()).await, json!({ "id": 3 }));
+}
+
+#[tokio::test]
+async fn log_compaction_keeps_the_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("schemas.log");
+
+    let repository = LogRepository::open(&path).await.unwrap();
+    let app = log_registry(repository.clone());
+    for _ in 0..3 {
+        register(&app, "employee", employee_v1()).await;
+        register(&app, "employee", employee_v2()).await;
+        call(&app, "DELETE", "/subjects/employee", None).await;
+    }
+    register(&app, "employee", employee_v1()).await;
+
+    let before = std::fs::read_to_string(&path).unwrap().lines().count();
+    repository.compact().await.unwrap();
+    let after = std::fs::read_to_string(&path).unwrap().lines().count();
+    assert!(after < before);
+
+    // appends go to the compacted log
+    register(&app, "employee", employee_v2()).await;
+
+    let app = log_registry(LogRepository::open(&path).await.unwrap());
+
+    // the soft deleted versions keep their numbers
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([7, 8]));
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions?deleted=true", None).await;
+    assert_eq!(versions, json!([1, 2, 3, 4, 5, 6, 7, 8]));
+
+    let (_, res) = call(&app, "GET", "/subjects/employee/versions/8", None).await;
+    assert_eq!(res["id"], json!(2));
+}
+
+#[tokio::test]
+async fn log_drops_a_torn_record() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("schemas.log");
+
+    let app = log_registry(LogRepository::open(&path).await.unwrap());
+    register(&app, "employee", employee_v1()).await;
+
+    let mut content = std::fs::read(&path).unwrap();
+    content.extend_from_slice(b"{\"type\":\"SUBJ");
+    std::fs::write(&path, content).unwrap();
+
+    let app = log_registry(LogRepository::open(&path).await.unwrap());
+    register(&app, "employee", employee_v2()).await;
+
+    let app = log_registry(LogRepository::open(&path).await.unwrap());
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1, 2]));
+}
+
+fn schema_record(subject: &str, version: i32, id: i64, schema: &Value, deleted: bool) -> String {
+    json!({
+        "key": { "keytype": "SCHEMA", "subject": subject, "version": version, "magic": 1 },
+        "value": { "subject": subject, "version": version, "id": id, "schema": schema["schema"], "deleted": deleted }
+    }).to_string()
+}
+
+#[tokio::test]
+async fn import_keeps_ids_and_versions() {
+    let app = registry();
+
+    let records = [
+        json!({ "key": { "keytype": "CONFIG", "subject": "employee", "magic": 0 }, "value": { "compatibilityLevel": "FULL" } }).to_string(),
+        json!({ "key": { "keytype": "CONFIG", "subject": "gone", "magic": 0 }, "value": { "compatibilityLevel": "NONE" } }).to_string(),
+        schema_record("employee", 3, 10, &employee_v1(), false),
+        schema_record("employee", 4, 11, &employee_incompatible(), false),
+        schema_record("person", 1, 10, &employee_v1(), false),
+        schema_record("person", 2, 12, &employee_v2(), false),
+        schema_record("person", 2, 12, &employee_v2(), true),
+        json!({ "key": { "keytype": "NOOP", "magic": 0 }, "value": null }).to_string()
+    ].join("\n");
+
+    // the configs wait for the schemas creating their subjects, the one of a subject that never comes is skipped
+    let (status, res) = call_text(&app, "POST", "/admin/import", &records).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(serde_json::from_str::<Value>(&res).unwrap(), json!({ "imported": 6, "skipped": 2 }));
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([3, 4]));
+
+    let (_, res) = call(&app, "GET", "/subjects/employee/versions/latest", None).await;
+    assert_eq!(res["id"], json!(11));
+
+    let (_, versions) = call(&app, "GET", "/subjects/person/versions", None).await;
+    assert_eq!(versions, json!([1]));
+
+    let (_, res) = call(&app, "GET", "/config/employee", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "FULL" }));
+
+    // registrations continue after the imported ids and versions
+    assert_eq!(register(&app, "person", employee(json!([]))).await, json!({ "id": 13 }));
+    let (_, versions) = call(&app, "GET", "/subjects/person/versions", None).await;
+    assert_eq!(versions, json!([1, 3]));
+}
+
+#[tokio::test]
+async fn import_purges_tombstoned_versions() {
+    let app = registry();
+
+    let records = [
+        schema_record("employee", 1, 1, &employee_v1(), false),
+        schema_record("employee", 2, 2, &employee_v2(), false),
+        json!({ "key": { "keytype": "SCHEMA", "subject": "employee", "version": 1, "magic": 1 }, "value": null }).to_string()
+    ].join("\n");
+
+    let (status, res) = call_text(&app, "POST", "/admin/import", &records).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(serde_json::from_str::<Value>(&res).unwrap(), json!({ "imported": 3, "skipped": 0 }));
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions?deleted=true", None).await;
+    assert_eq!(versions, json!([2]));
+
+    let (status, _) = call(&app, "GET", "/schemas/ids/1", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn import_replays_deletes_without_checks() {
+    let app = registry();
+    call(&app, "PUT", "/mode", Some(json!({ "mode": "READONLY" }))).await;
+
+    // the referenced subject was deleted after the one referencing it, but its records come first
+    let address = json!({ "type": "record", "name": "Address", "namespace": "test", "fields": [{ "name": "street", "type": "string" }] });
+    let employee = json!({ "type": "record", "name": "Employee", "namespace": "other", "fields": [{ "name": "home", "type": "test.Address" }] });
+    let records = [
+        schema_record("address", 1, 1, &json!({ "schema": address.to_string() }), true),
+        json!({
+            "key": { "keytype": "SCHEMA", "subject": "other", "version": 1, "magic": 1 },
+            "value": { "subject": "other", "version": 1, "id": 2, "schema": employee.to_string(), "references": [{ "name": "test.Address", "subject": "address", "version": 1 }], "deleted": true }
+        }).to_string(),
+        json!({ "key": { "keytype": "DELETE_SUBJECT", "subject": "address", "magic": 0 }, "value": { "subject": "address", "version": 1 } }).to_string(),
+        json!({ "key": { "keytype": "DELETE_SUBJECT", "subject": "other", "magic": 0 }, "value": { "subject": "other", "version": 1 } }).to_string()
+    ].join("\n");
+
+    let (status, res) = call_text(&app, "POST", "/admin/import", &records).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(serde_json::from_str::<Value>(&res).unwrap(), json!({ "imported": 4, "skipped": 0 }));
+
+    let (_, subjects) = call(&app, "GET", "/subjects", None).await;
+    assert_eq!(subjects, json!([]));
+
+    let (_, subjects) = call(&app, "GET", "/subjects?deleted=true", None).await;
+    assert_eq!(subjects, json!(["address", "other"]));
+}
+
+#[tokio::test]
+async fn import_reads_every_record_before_writing() {
+    let app = registry();
+
+    let mode = json!({ "key": { "keytype": "MODE", "magic": 0 }, "value": { "mode": "READWRITE_OVERRIDE" } }).to_string();
+    let records = [
+        schema_record("employee", 1, 1, &employee_v1(), false),
+        mode.clone(),
+        json!({ "key": { "keytype": "SCHEMA", "subject": "employee", "version": 2, "magic": 1 }, "value": { "subject": "employee" } }).to_string()
+    ].join("\n");
+
+    let (status, res) = call_text(&app, "POST", "/admin/import", &records).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(res.contains("Record on line 3"), "{}", res);
+    let (_, subjects) = call(&app, "GET", "/subjects", None).await;
+    assert_eq!(subjects, json!([]));
+
+    // a record that fails once written leaves the ones before it, and the file can be imported again once fixed
+    let records = [schema_record("employee", 1, 1, &employee_v1(), false), schema_record("employee", 2, 1, &employee_v2(), false), mode.clone()].join("\n");
+    let (status, _) = call_text(&app, "POST", "/admin/import", &records).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let records = [schema_record("employee", 1, 1, &employee_v1(), false), schema_record("employee", 2, 2, &employee_v2(), false), mode].join("\n");
+    let (status, res) = call_text(&app, "POST", "/admin/import", &records).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(serde_json::from_str::<Value>(&res).unwrap(), json!({ "imported": 3, "skipped": 0 }));
+
+    let (_, versions) = call(&app, "GET", "/subjects/employee/versions", None).await;
+    assert_eq!(versions, json!([1, 2]));
+    let (_, res) = call(&app, "GET", "/mode", None).await;
+    assert_eq!(res, json!({ "mode": "READWRITE_OVERRIDE" }));
+}
+
+#[tokio::test]
+async fn import_rejects_conflicting_ids() {
+    let app = registry();
+    register(&app, "employee", employee_v1()).await;
+
+    let (status, res) = call_text(&app, "POST", "/admin/import", &schema_record("person", 1, 1, &employee_v2(), false)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(serde_json::from_str::<Value>(&res).unwrap()["error_code"], json!(42205));
+
+    let records = [schema_record("person", 1, 2, &employee_v2(), false), schema_record("person", 2, 5, &employee_v1(), false)].join("\n");
+    let (status, res) = call_text(&app, "POST", "/admin/import", &records).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(serde_json::from_str::<Value>(&res).unwrap(), json!({ "error_code": 42207, "message": "Record on line 2: Schema already registered with id 1 instead of input id 5" }));
+
+    let (status, _) = call_text(&app, "POST", "/admin/import", "{}").await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn export_imports_into_another_registry() {
+    let app = registry();
+
+    let address = json!({ "type": "record", "name": "Address", "namespace": "test", "fields": [{ "name": "street", "type": "string" }] });
+    register(&app, "address", json!({ "schema": address.to_string() })).await;
+    register(&app, "employee", employee_v1()).await;
+    register(&app, "employee", employee_v2()).await;
+    let employee = json!({ "type": "record", "name": "Employee", "namespace": "other", "fields": [{ "name": "home", "type": "test.Address" }] });
+    register(&app, "other", json!({ "schema": employee.to_string(), "references": [{ "name": "test.Address", "subject": "address", "version": 1 }] })).await;
+    register(&app, "person", employee_v1()).await;
+    call(&app, "PUT", "/config/employee", Some(json!({ "compatibility": "NONE" }))).await;
+    call(&app, "PUT", "/config/person", Some(json!({ "compatibility": "FULL" }))).await;
+    call(&app, "PUT", "/mode/person", Some(json!({ "mode": "IMPORT" }))).await;
+    call(&app, "PUT", "/config/staff", Some(json!({ "alias": "employee" }))).await;
+    call(&app, "DELETE", "/subjects/employee/versions/1", None).await;
+    call(&app, "DELETE", "/subjects/person", None).await;
+    call(&app, "PUT", "/mode/employee", Some(json!({ "mode": "READONLY" }))).await;
+
+    // the soft deleted versions and subjects come along, with the settings of deleted and reserved subjects
+    let (status, records) = call_text(&app, "GET", "/admin/export", "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(records.lines().count(), 11);
+
+    let copy = registry();
+    let (status, _) = call_text(&copy, "POST", "/admin/import", &records).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, exported) = call_text(&copy, "GET", "/admin/export", "").await;
+    assert_eq!(exported, records);
+
+    let (_, res) = call(&copy, "GET", "/subjects/employee/versions/2", None).await;
+    assert_eq!(res["id"], json!(3));
+
+    let (_, versions) = call(&copy, "GET", "/subjects/employee/versions?deleted=true", None).await;
+    assert_eq!(versions, json!([1, 2]));
+
+    let (status, _) = call(&copy, "GET", "/subjects/person/versions", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, res) = call(&copy, "GET", "/subjects/staff/versions/latest", None).await;
+    assert_eq!(res["id"], json!(3));
+
+    let (_, res) = call(&copy, "GET", "/config/person", None).await;
+    assert_eq!(res, json!({ "compatibilityLevel": "FULL" }));
+
+    let (_, res) = call(&copy, "GET", "/mode/employee", None).await;
+    assert_eq!(res, json!({ "mode": "READONLY" }));
+}